@@ -0,0 +1,33 @@
+#!/usr/bin/env python
+
+import re
+import json
+import unicodedata
+
+import redis
+
+
+def tokenize(name):
+    folded = ''.join(
+        c for c in unicodedata.normalize('NFKD', name)
+        if not unicodedata.combining(c)).lower()
+    return [token for token in re.split(r'[^\w]|_', folded) if token]
+
+
+def main():
+    matcher = re.compile('group:([^:]+):user:([^:]+)$')
+    database = redis.Redis(decode_responses=True)
+    for key in database.scan_iter():
+        match = matcher.match(key)
+        if match:
+            group_id, user_id = match.groups()
+            user = json.loads(database.get(key))
+            name = user['item']['name']
+            index_key = 'group:' + group_id + ':user.name.token.index'
+            for token in tokenize(name):
+                database.zadd(index_key, {token + ':' + user_id: 0})
+            print(name, tokenize(name))
+
+
+if __name__ == '__main__':
+    main()
@@ -0,0 +1,31 @@
+#!/usr/bin/env python
+
+import re
+import unicodedata
+
+import redis
+
+
+def normalize(name):
+    return ''.join(
+        c for c in unicodedata.normalize('NFKD', name)
+        if not unicodedata.combining(c)).lower()
+
+
+def main():
+    matcher = re.compile('group:[^:]+:user.name.index$')
+    database = redis.Redis(decode_responses=True)
+    for key in database.scan_iter():
+        if matcher.match(key):
+            for entry in database.zrange(key, 0, -1):
+                # Names may contain ':', the user ID never does.
+                name, user_id = entry.rsplit(':', 1)
+                normalized_entry = normalize(name) + ':' + user_id
+                if normalized_entry != entry:
+                    database.zrem(key, entry)
+                    database.zadd(key, {normalized_entry: 0})
+                    print(entry, '->', normalized_entry)
+
+
+if __name__ == '__main__':
+    main()
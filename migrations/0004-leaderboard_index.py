@@ -0,0 +1,34 @@
+#!/usr/bin/env python
+
+import re
+import json
+import math
+
+import redis
+
+
+def score(skill):
+    sigma2 = 1.0 / skill['pi']
+    mu = skill['tau'] * sigma2
+    return mu - 2.0 * math.sqrt(sigma2)
+
+
+def main():
+    matcher = re.compile('group:([^:]+):user:([^:]+)$')
+    database = redis.Redis(decode_responses=True)
+    for key in database.scan_iter():
+        match = matcher.match(key)
+        if match:
+            group_id, user_id = match.groups()
+            user = json.loads(database.get(key))
+            # Merged users are ranked with the user they were merged into.
+            if user['parent_index'] != user_id:
+                continue
+            user_score = score(user['item']['player']['skill'])
+            index_key = 'group:' + group_id + ':leaderboard.index'
+            database.zadd(index_key, {user_id: user_score})
+            print(user_id, user_score)
+
+
+if __name__ == '__main__':
+    main()
@@ -0,0 +1,29 @@
+#!/usr/bin/env python
+
+import re
+import json
+
+import redis
+
+
+def main():
+    matcher = re.compile('group:([^:]+):leagues$')
+    database = redis.Redis(decode_responses=True)
+    for key in database.scan_iter():
+        match = matcher.match(key)
+        if match:
+            group_id = match.group(1)
+            for league_id, value in database.hgetall(key).items():
+                league = json.loads(value)
+                # Finished leagues take no more games.
+                if all(fixture['game_id'] is not None for fixture in league['fixtures']):
+                    continue
+                for user_ids in league['participants']:
+                    for user_id in user_ids:
+                        user_leagues_key = 'group:' + group_id + ':user.leagues:' + user_id
+                        database.sadd(user_leagues_key, league_id)
+                        print(user_id, league_id)
+
+
+if __name__ == '__main__':
+    main()
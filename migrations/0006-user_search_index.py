@@ -0,0 +1,47 @@
+#!/usr/bin/env python
+
+import re
+import json
+import unicodedata
+
+import redis
+
+
+def normalize(name):
+    return ''.join(
+        c for c in unicodedata.normalize('NFKD', name)
+        if not unicodedata.combining(c)).lower()
+
+
+def tokenize(name):
+    return [token for token in re.split(r'[^\w]|_', normalize(name)) if token]
+
+
+def main():
+    matcher = re.compile('group:([^:]+):user:([^:]+)$')
+    database = redis.Redis(decode_responses=True)
+    # Token index entries now carry the normalized name, so they are rebuilt.
+    for key in database.scan_iter('group:*:user.name.token.index'):
+        database.delete(key)
+    for key in database.scan_iter():
+        match = matcher.match(key)
+        if match:
+            group_id, user_id = match.groups()
+            user = json.loads(database.get(key))
+            name = user['item']['name']
+            index_entry = normalize(name) + ':' + user_id
+            index_key = 'group:' + group_id + ':user.name.token.index'
+            for token in tokenize(name):
+                database.zadd(index_key, {token + ':' + index_entry: 0})
+
+            # Users that never played count as least recently active.
+            games_key = 'group:' + group_id + ':user.games:' + user_id
+            last_games = database.zrevrange(games_key, 0, 0, withscores=True)
+            activity = last_games[0][1] if last_games else 0
+            activity_key = 'group:' + group_id + ':user.activity'
+            database.zadd(activity_key, {user_id: activity})
+            print(name, tokenize(name), activity)
+
+
+if __name__ == '__main__':
+    main()
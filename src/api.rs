@@ -1,39 +1,230 @@
+use std::collections::HashMap;
+
+use chrono::TimeZone;
 use rocket::{
+    delete,
+    futures::StreamExt,
     get,
-    http::Status,
-    post,
-    request::Request,
-    response::{self, Responder},
+    http::{ContentType, Status},
+    post, put,
+    request::{self, FromRequest, Request},
+    response::{
+        self,
+        stream::{Event, EventStream},
+        Responder, Response,
+    },
     serde::{json::Json, Deserialize, Serialize},
-    State,
+    tokio::select,
+    Shutdown, State,
 };
 use rocket_db_pools::Connection;
 
+use crate::balance::Lineup;
+use crate::group_settings::{GameError, GroupSettings};
+use crate::league::{League, Scoring, TableRow};
 use crate::merge;
 use crate::message::Message;
-use crate::skill_base::{self, decode_and_validate_group_id, Error, GameId, UserId};
+use crate::rating_system::{RatingSystemKind, Summary};
+use crate::skill_base::{
+    self, decode_and_validate_group_id, Error, GameId, LeagueId, Position, Side, SkillKind,
+    TournamentId, UserId, WebhookId,
+};
+use crate::statistics;
 use crate::store::Store;
+use crate::tournament::{Format, Tournament};
+use crate::webhook::{self, DeliveryStatus};
+
+/// Takes IDs from path segments as they are.
+macro_rules! id_from_param {
+    ($($id:ident),*) => {
+        $(
+            impl<'r> rocket::request::FromParam<'r> for $id {
+                type Error = &'r str;
+
+                fn from_param(param: &'r str) -> Result<Self, Self::Error> {
+                    Ok($id::from(param.to_string()))
+                }
+            }
+        )*
+    };
+}
 
-impl<'r> rocket::request::FromParam<'r> for UserId {
-    type Error = &'r str;
+id_from_param!(UserId, WebhookId, TournamentId, LeagueId);
 
-    fn from_param(param: &'r str) -> Result<Self, Self::Error> {
-        Ok(UserId::from(param.to_string()))
+/// Problem document that describes why a request failed.
+#[derive(Serialize, Debug)]
+struct Problem {
+    /// Stable, machine-readable identifier of the problem.
+    code: &'static str,
+    /// Human-readable description of the problem.
+    message: String,
+    /// Problem specific information, e.g. which rule a request violated.
+    #[serde(skip_serializing_if = "serde_json::Value::is_null")]
+    details: serde_json::Value,
+}
+
+impl Problem {
+    fn new(code: &'static str, message: impl Into<String>) -> Self {
+        Problem {
+            code,
+            message: message.into(),
+            details: serde_json::Value::Null,
+        }
+    }
+
+    fn with_details(mut self, details: impl Serialize) -> Self {
+        self.details = serde_json::to_value(details).unwrap_or(serde_json::Value::Null);
+        self
     }
 }
 
-impl<'r> Responder<'r, 'static> for Error {
-    fn respond_to(self, _: &'r Request<'_>) -> response::Result<'static> {
-        match self {
-            Error::UserAlreadyExists => Err(Status::Conflict),
-            Error::UserNameTooShort => Err(Status::BadRequest),
-            Error::Merge(merge::Error::MissingEntryError(_)) => Err(Status::NotFound),
-            Error::InvalidGroupId => Err(Status::BadRequest),
-            err => {
-                println!("{:?}", err);
-                Err(Status::InternalServerError)
-            }
+/// Maps an error to its HTTP status and problem document.
+fn into_problem(err: Error) -> (Status, Problem) {
+    match err {
+        Error::Redis(err) => {
+            rocket::error!("data store failed: {:?}", err);
+            (
+                Status::ServiceUnavailable,
+                Problem::new("store_unavailable", "the data store is not available"),
+            )
+        }
+        Error::Merge(merge::Error::MissingEntryError(user_id)) => (
+            Status::NotFound,
+            Problem::new("user_not_found", "the user does not exist")
+                .with_details(serde_json::json!({ "user_id": user_id })),
+        ),
+        Error::Merge(merge::Error::NoParentError(user_id)) => {
+            rocket::error!("missing parent for user {:?}", user_id);
+            (
+                Status::InternalServerError,
+                Problem::new("user_record_corrupted", "the stored user is inconsistent")
+                    .with_details(serde_json::json!({ "user_id": user_id })),
+            )
         }
+        Error::InvalidUserName(err) => (
+            Status::BadRequest,
+            Problem::new("invalid_user_name", err.to_string()).with_details(err),
+        ),
+        Error::UserAlreadyExists => (
+            Status::Conflict,
+            Problem::new(
+                "user_already_exists",
+                "a user with this name already exists",
+            )
+            .with_details(serde_json::json!({ "rule": "unique" })),
+        ),
+        Error::EmptyQuery => (
+            Status::BadRequest,
+            Problem::new("empty_query", "the query needs at least one word"),
+        ),
+        Error::IdempotencyKeyReused => (
+            Status::Conflict,
+            Problem::new(
+                "idempotency_key_reused",
+                "the idempotency key was already used for a different game",
+            ),
+        ),
+        Error::InvalidGame(err) => (
+            Status::BadRequest,
+            Problem::new("invalid_game", err.to_string()).with_details(err),
+        ),
+        Error::InvalidBatchGame(index, err) => {
+            let message = format!("game at position {}: {}", index, err);
+            let mut details = serde_json::to_value(err).unwrap_or(serde_json::Value::Null);
+            details["index"] = index.into();
+            (
+                Status::BadRequest,
+                Problem::new("invalid_game", message).with_details(details),
+            )
+        }
+        Error::InvalidGroupSettings(err) => (
+            Status::UnprocessableEntity,
+            Problem::new("invalid_group_settings", err.to_string()).with_details(err),
+        ),
+        Error::InvalidGroupId => (
+            Status::BadRequest,
+            Problem::new("invalid_group_id", "the group does not exist"),
+        ),
+        Error::UnknownGameMode => (
+            Status::NotFound,
+            Problem::new("unknown_game_mode", "the group has no such game mode"),
+        ),
+        Error::AmbiguousSkillKind => (
+            Status::BadRequest,
+            Problem::new(
+                "ambiguous_leaderboard",
+                "a leaderboard is either for a game mode or for a position",
+            ),
+        ),
+        Error::SeasonLeaderboardNotArchived => (
+            Status::NotFound,
+            Problem::new(
+                "season_leaderboard_not_archived",
+                "past seasons only archived the leaderboard across all games",
+            ),
+        ),
+        Error::InvalidPlayerCount => (
+            Status::BadRequest,
+            Problem::new(
+                "invalid_player_count",
+                "the players cannot be split into two teams",
+            ),
+        ),
+        Error::NotEnoughQueued => (
+            Status::Conflict,
+            Problem::new(
+                "not_enough_players",
+                "not enough players are waiting for a match",
+            ),
+        ),
+        Error::InvalidSeasonReset => (
+            Status::BadRequest,
+            Problem::new(
+                "invalid_season_reset",
+                "the reset has to be between 0 and 1",
+            ),
+        ),
+        Error::SeasonNotFound => (
+            Status::NotFound,
+            Problem::new("season_not_found", "the season does not exist"),
+        ),
+        Error::InvalidTournament(err) => (
+            Status::BadRequest,
+            Problem::new("invalid_tournament", err.to_string()).with_details(err),
+        ),
+        Error::TournamentNotFound => (
+            Status::NotFound,
+            Problem::new("tournament_not_found", "the tournament does not exist"),
+        ),
+        Error::InvalidLeague(err) => (
+            Status::BadRequest,
+            Problem::new("invalid_league", err.to_string()).with_details(err),
+        ),
+        Error::LeagueNotFound => (
+            Status::NotFound,
+            Problem::new("league_not_found", "the league does not exist"),
+        ),
+        Error::InvalidWebhookUrl => (
+            Status::BadRequest,
+            Problem::new(
+                "invalid_webhook_url",
+                "the webhook URL has to be an HTTP(S) URL",
+            ),
+        ),
+        Error::WebhookNotFound => (
+            Status::NotFound,
+            Problem::new("webhook_not_found", "the webhook does not exist"),
+        ),
+    }
+}
+
+impl<'r> Responder<'r, 'static> for Error {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        let (status, problem) = into_problem(self);
+        Response::build_from(Json(problem).respond_to(request)?)
+            .status(status)
+            .header(ContentType::new("application", "problem+json"))
+            .ok()
     }
 }
 
@@ -43,6 +234,11 @@ struct Game {
     winner_ids: Vec<UserId>,
     loser_ids: Vec<UserId>,
     timestamp: u128,
+    mode: Option<String>,
+    positions: HashMap<UserId, Position>,
+    weights: HashMap<UserId, f64>,
+    table: Option<String>,
+    winner_side: Option<Side>,
 }
 
 impl From<skill_base::Game> for Game {
@@ -52,6 +248,11 @@ impl From<skill_base::Game> for Game {
             winner_ids: game.winner_ids().clone(),
             loser_ids: game.loser_ids().clone(),
             timestamp: game.datetime().naive_utc().timestamp_millis() as u128,
+            mode: game.mode().map(str::to_owned),
+            positions: game.positions().clone(),
+            weights: game.weights().clone(),
+            table: game.table().map(str::to_owned),
+            winner_side: game.winner_side(),
         }
     }
 }
@@ -68,18 +269,80 @@ struct Player {
     skill: Message,
 }
 
-impl From<skill_base::User> for User {
-    fn from(user: skill_base::User) -> Self {
+impl User {
+    /// Shows a user with one kind of their skills.
+    ///
+    /// # Arguments
+    ///
+    /// * `user` user to show; needs to have the kind of skill.
+    /// * `kind` kind of the skill to show.
+    fn with_skill(user: skill_base::User, kind: SkillKind) -> Self {
+        let player = user.player_in(kind).unwrap();
+        let skill = player
+            .skill_at(&chrono::Utc::now())
+            .unwrap_or(*player.skill());
         User {
             id: user.id().clone(),
             name: user.name().to_owned(),
+            player: Player { skill },
+        }
+    }
+}
+
+impl From<skill_base::User> for User {
+    fn from(user: skill_base::User) -> Self {
+        User::with_skill(user, SkillKind::Overall)
+    }
+}
+
+impl From<skill_base::Standing> for User {
+    fn from(standing: skill_base::Standing) -> Self {
+        User {
+            id: standing.user_id().clone(),
+            name: standing.name().to_owned(),
             player: Player {
-                skill: user.player().skill_at(&chrono::Utc::now()).unwrap(),
+                skill: *standing.skill(),
             },
         }
     }
 }
 
+#[derive(Serialize, Debug)]
+struct Season {
+    number: u32,
+    name: String,
+    start: u128,
+    end: Option<u128>,
+}
+
+impl From<skill_base::Season> for Season {
+    fn from(season: skill_base::Season) -> Self {
+        Season {
+            number: season.number(),
+            name: season.name().to_owned(),
+            start: season.start().naive_utc().timestamp_millis() as u128,
+            end: season
+                .end()
+                .map(|end| end.naive_utc().timestamp_millis() as u128),
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+struct UserRatings {
+    user: User,
+    ratings: HashMap<RatingSystemKind, Summary>,
+}
+
+impl From<skill_base::UserRatings> for UserRatings {
+    fn from(ratings: skill_base::UserRatings) -> Self {
+        UserRatings {
+            ratings: ratings.ratings().clone(),
+            user: ratings.user().clone().into(),
+        }
+    }
+}
+
 fn into_users(users: Vec<skill_base::User>) -> Vec<User> {
     users
         .into_iter()
@@ -87,15 +350,46 @@ fn into_users(users: Vec<skill_base::User>) -> Vec<User> {
         .collect::<Vec<_>>()
 }
 
+/// Optional client-supplied key that identifies retries of the same request.
+pub struct IdempotencyKey(Option<String>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for IdempotencyKey {
+    type Error = std::convert::Infallible;
+
+    async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        request::Outcome::Success(IdempotencyKey(
+            request
+                .headers()
+                .get_one("Idempotency-Key")
+                .map(|key| key.to_owned()),
+        ))
+    }
+}
+
 #[derive(Deserialize, Debug)]
 pub struct PostGameRequest {
     winner_ids: Vec<UserId>,
     loser_ids: Vec<UserId>,
+    /// Optional positions of all players.
+    #[serde(default)]
+    positions: HashMap<UserId, Position>,
+    /// Optional fractions of the game players played if they were
+    /// substituted.
+    #[serde(default)]
+    weights: HashMap<UserId, f64>,
+    /// Optional side of the table the winners played on.
+    winner_side: Option<Side>,
+    /// Optional table the game was played on. Only allowed together with
+    /// `winner_side`.
+    table: Option<String>,
 }
 
 #[derive(Serialize, Debug)]
 pub struct PostGameResponse {
     game: Game,
+    /// Ratings of all players before and after the game.
+    rating_changes: Vec<skill_base::RatingChange>,
 }
 
 #[derive(Deserialize)]
@@ -103,25 +397,128 @@ pub struct GroupKeyConfig {
     pub group_key: skill_base::GroupKey,
 }
 
+/// Sets the side of the winners and the table of a submitted game. Games only
+/// have a table together with the side of the winners.
+fn with_side(
+    game: skill_base::Game,
+    winner_side: Option<Side>,
+    table: Option<String>,
+) -> Result<skill_base::Game, GameError> {
+    match (winner_side, table) {
+        (Some(winner_side), table) => Ok(game.with_side(table, winner_side)),
+        (None, Some(_)) => Err(GameError::TableWithoutSide),
+        (None, None) => Ok(game),
+    }
+}
+
+/// Creates a game. Retries that carry the same `Idempotency-Key` header as an
+/// earlier request return the original response. Reusing the key for a
+/// different game is a conflict.
 #[post("/<secret_group_id>/games", data = "<request>")]
 pub async fn post_game(
     mut store: Connection<Store>,
     group_key_config: &State<GroupKeyConfig>,
     secret_group_id: String,
+    idempotency_key: IdempotencyKey,
     request: Json<PostGameRequest>,
 ) -> Result<Json<PostGameResponse>, Error> {
     let group_id = decode_and_validate_group_id(&group_key_config.group_key, secret_group_id)?;
-    let game_id = GameId::from(uuid::Uuid::new_v4().simple().to_string());
-    skill_base::create_game(
-        &mut store,
-        &group_id,
-        &game_id,
-        &request.winner_ids,
-        &request.loser_ids,
+    let request = request.into_inner();
+    let game = skill_base::Game::new(
+        GameId::from(uuid::Uuid::new_v4().simple().to_string()),
         chrono::Utc::now(),
+        request.winner_ids,
+        request.loser_ids,
     )
-    .await
-    .map(|game| Json(PostGameResponse { game: game.into() }))
+    .with_positions(request.positions)
+    .with_weights(request.weights);
+    let game = with_side(game, request.winner_side, request.table)?;
+    let (game, rating_changes) =
+        skill_base::create_game(&mut store, &group_id, game, idempotency_key.0.as_deref()).await?;
+    Ok(Json(PostGameResponse {
+        game: game.into(),
+        rating_changes,
+    }))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct BatchGame {
+    winner_ids: Vec<UserId>,
+    loser_ids: Vec<UserId>,
+    /// Milliseconds since the Unix epoch when the game took place. Must not be
+    /// in the future.
+    timestamp: i64,
+    /// Optional positions of all players.
+    #[serde(default)]
+    positions: HashMap<UserId, Position>,
+    /// Optional fractions of the game players played if they were
+    /// substituted.
+    #[serde(default)]
+    weights: HashMap<UserId, f64>,
+    /// Optional side of the table the winners played on.
+    winner_side: Option<Side>,
+    /// Optional table the game was played on. Only allowed together with
+    /// `winner_side`.
+    table: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct PostGamesBatchRequest {
+    games: Vec<BatchGame>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct PostGamesBatchResponse {
+    games: Vec<Game>,
+    /// Ratings of all players before their first and after their last game.
+    rating_changes: Vec<skill_base::RatingChange>,
+}
+
+/// Creates several games at once. The games are applied in chronological order
+/// and either all of them or none get created.
+///
+/// Games cannot be inserted into the history of a player. A game that took
+/// place before the latest recorded game of one of its players is rejected
+/// with the `backdated_game` rule, so results have to be entered before the
+/// players play again.
+#[post("/<secret_group_id>/games:batch", data = "<request>")]
+pub async fn post_games_batch(
+    mut store: Connection<Store>,
+    group_key_config: &State<GroupKeyConfig>,
+    secret_group_id: String,
+    request: Json<PostGamesBatchRequest>,
+) -> Result<Json<PostGamesBatchResponse>, Error> {
+    let group_id = decode_and_validate_group_id(&group_key_config.group_key, secret_group_id)?;
+    // Games from the future would date the skills of their players past now.
+    let now = chrono::Utc::now();
+    let mut games = Vec::new();
+    for (index, game) in request.into_inner().games.into_iter().enumerate() {
+        let datetime = chrono::Utc
+            .timestamp_millis_opt(game.timestamp)
+            .single()
+            .filter(|datetime| *datetime <= now)
+            .ok_or(Error::InvalidBatchGame(index, GameError::InvalidTimestamp))?;
+        let batch_game = skill_base::Game::new(
+            GameId::from(uuid::Uuid::new_v4().simple().to_string()),
+            datetime,
+            game.winner_ids,
+            game.loser_ids,
+        )
+        .with_positions(game.positions)
+        .with_weights(game.weights);
+        games.push(
+            with_side(batch_game, game.winner_side, game.table)
+                .map_err(|err| Error::InvalidBatchGame(index, err))?,
+        );
+    }
+    skill_base::create_games(&mut store, &group_id, &games)
+        .await
+        .map(|(games, rating_changes)| {
+            Json(PostGamesBatchResponse {
+                games: games.into_iter().map(Game::from).collect(),
+                rating_changes,
+            })
+        })
 }
 
 #[derive(Serialize, Debug)]
@@ -129,15 +526,20 @@ pub struct GetGamesResponse {
     games: Vec<Game>,
 }
 
-#[get("/<secret_group_id>/games?<before>")]
+#[get("/<secret_group_id>/games?<before>&<season>")]
 pub async fn get_games(
     mut store: Connection<Store>,
     group_key_config: &State<GroupKeyConfig>,
     secret_group_id: String,
     before: Option<GameId>,
+    season: Option<u32>,
 ) -> Result<Json<GetGamesResponse>, Error> {
     let group_id = decode_and_validate_group_id(&group_key_config.group_key, secret_group_id)?;
-    skill_base::list_games(&mut store, &group_id, &before)
+    let season = match season {
+        Some(number) => Some(skill_base::find_season(&mut store, &group_id, number).await?),
+        None => None,
+    };
+    skill_base::list_games(&mut store, &group_id, &before, season.as_ref())
         .await
         .map(|games| {
             Json(GetGamesResponse {
@@ -220,12 +622,10 @@ pub async fn get_user_games(
 
     let mut joined_games = Vec::new();
     for game in games {
-        let winners = into_users(
-            skill_base::read_users(&mut store, &group_id, &game.clone().winner_ids()).await?,
-        );
-        let losers = into_users(
-            skill_base::read_users(&mut store, &group_id, &game.clone().loser_ids()).await?,
-        );
+        let winners =
+            into_users(skill_base::read_users(&mut store, &group_id, game.winner_ids()).await?);
+        let losers =
+            into_users(skill_base::read_users(&mut store, &group_id, game.loser_ids()).await?);
         joined_games.push(JoinedGame { winners, losers });
     }
 
@@ -264,18 +664,903 @@ pub struct GetLeaderboardResponse {
     users: Vec<User>,
 }
 
-#[get("/<secret_group_id>/leaderboard")]
+/// Ranks users by their skill across all games, in a game mode or in a
+/// position. Only one of `mode` and `position` can be given.
+///
+/// The overall leaderboard lists the top 100 users in the order of the ranks
+/// that game responses report.
+///
+/// Past seasons show the archived leaderboard at their end.
+#[get("/<secret_group_id>/leaderboard?<mode>&<position>&<season>")]
 pub async fn get_leaderboard(
     mut store: Connection<Store>,
     group_key_config: &State<GroupKeyConfig>,
     secret_group_id: String,
+    mode: Option<String>,
+    position: Option<Position>,
+    season: Option<u32>,
 ) -> Result<Json<GetLeaderboardResponse>, Error> {
     let group_id = decode_and_validate_group_id(&group_key_config.group_key, secret_group_id)?;
-    skill_base::get_leaderboard(&mut store, &group_id, &chrono::Utc::now())
+    let kind = match (mode.as_deref(), position) {
+        (None, None) => SkillKind::Overall,
+        (Some(mode), None) => SkillKind::Mode(mode),
+        (None, Some(position)) => SkillKind::Position(position),
+        (Some(_), Some(_)) => return Err(Error::AmbiguousSkillKind),
+    };
+
+    if let Some(number) = season {
+        let season = skill_base::find_season(&mut store, &group_id, number).await?;
+        if season.end().is_some() {
+            if !matches!(kind, SkillKind::Overall) {
+                return Err(Error::SeasonLeaderboardNotArchived);
+            }
+            let standings =
+                skill_base::read_season_standings(&mut store, &group_id, &season).await?;
+            return Ok(Json(GetLeaderboardResponse {
+                users: standings.into_iter().map(User::from).collect(),
+            }));
+        }
+    }
+
+    skill_base::get_leaderboard(&mut store, &group_id, &chrono::Utc::now(), kind)
         .await
         .map(|users| {
             Json(GetLeaderboardResponse {
-                users: users.into_iter().map(User::from).collect(),
+                users: users
+                    .into_iter()
+                    .map(|user| User::with_skill(user, kind))
+                    .collect(),
+            })
+        })
+}
+
+#[derive(Serialize, Debug)]
+pub struct GetSmoothedLeaderboardResponse {
+    /// Milliseconds since the Unix epoch when the skills were last smoothed.
+    smoothed_at: Option<u128>,
+    users: Vec<User>,
+}
+
+/// Ranks users by their skills smoothed over the whole game history, which
+/// get recomputed by a batch job.
+#[get("/<secret_group_id>/leaderboard:smoothed")]
+pub async fn get_smoothed_leaderboard(
+    mut store: Connection<Store>,
+    group_key_config: &State<GroupKeyConfig>,
+    secret_group_id: String,
+) -> Result<Json<GetSmoothedLeaderboardResponse>, Error> {
+    let group_id = decode_and_validate_group_id(&group_key_config.group_key, secret_group_id)?;
+    skill_base::get_smoothed_leaderboard(&mut store, &group_id, &chrono::Utc::now())
+        .await
+        .map(|(smoothed_at, users)| {
+            Json(GetSmoothedLeaderboardResponse {
+                smoothed_at: smoothed_at
+                    .map(|smoothed_at| smoothed_at.naive_utc().timestamp_millis() as u128),
+                users: into_users(users),
             })
         })
 }
+
+#[derive(Serialize, Debug)]
+pub struct GetRatingsResponse {
+    users: Vec<UserRatings>,
+}
+
+/// Rates all games of the group with several rating systems for comparison.
+/// Without `system` these are TrueSkill and the rating systems in the group
+/// settings.
+#[get("/<secret_group_id>/ratings?<system>")]
+pub async fn get_ratings(
+    mut store: Connection<Store>,
+    group_key_config: &State<GroupKeyConfig>,
+    secret_group_id: String,
+    system: Option<RatingSystemKind>,
+) -> Result<Json<GetRatingsResponse>, Error> {
+    let group_id = decode_and_validate_group_id(&group_key_config.group_key, secret_group_id)?;
+    let systems = match system {
+        Some(system) => vec![system],
+        None => {
+            let settings = skill_base::read_group_settings(&mut store, &group_id).await?;
+            let mut systems = vec![RatingSystemKind::TrueSkill];
+            for system in settings.rating_systems {
+                if !systems.contains(&system) {
+                    systems.push(system);
+                }
+            }
+            systems
+        }
+    };
+
+    skill_base::compare_ratings(&mut store, &group_id, &systems)
+        .await
+        .map(|users| {
+            Json(GetRatingsResponse {
+                users: users.into_iter().map(UserRatings::from).collect(),
+            })
+        })
+}
+
+#[derive(Deserialize, Debug)]
+pub struct PostTeamsBalanceRequest {
+    user_ids: Vec<UserId>,
+}
+
+/// Proposes the most even teams for a game between the given users.
+#[post("/<secret_group_id>/teams:balance", data = "<request>")]
+pub async fn post_teams_balance(
+    mut store: Connection<Store>,
+    group_key_config: &State<GroupKeyConfig>,
+    secret_group_id: String,
+    request: Json<PostTeamsBalanceRequest>,
+) -> Result<Json<Lineup>, Error> {
+    let group_id = decode_and_validate_group_id(&group_key_config.group_key, secret_group_id)?;
+    skill_base::balance_teams(
+        &mut store,
+        &group_id,
+        &request.user_ids,
+        &chrono::Utc::now(),
+    )
+    .await
+    .map(Json)
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GroupSettingsMessage {
+    settings: GroupSettings,
+}
+
+#[get("/<secret_group_id>/settings")]
+pub async fn get_group_settings(
+    mut store: Connection<Store>,
+    group_key_config: &State<GroupKeyConfig>,
+    secret_group_id: String,
+) -> Result<Json<GroupSettingsMessage>, Error> {
+    let group_id = decode_and_validate_group_id(&group_key_config.group_key, secret_group_id)?;
+    skill_base::read_group_settings(&mut store, &group_id)
+        .await
+        .map(|settings| Json(GroupSettingsMessage { settings }))
+}
+
+#[put("/<secret_group_id>/settings", data = "<request>")]
+pub async fn put_group_settings(
+    mut store: Connection<Store>,
+    group_key_config: &State<GroupKeyConfig>,
+    secret_group_id: String,
+    request: Json<GroupSettingsMessage>,
+) -> Result<Json<GroupSettingsMessage>, Error> {
+    let group_id = decode_and_validate_group_id(&group_key_config.group_key, secret_group_id)?;
+    let request = request.into_inner();
+    skill_base::write_group_settings(&mut store, &group_id, &request.settings)
+        .await
+        .map(|()| Json(request))
+}
+
+#[derive(Serialize, Debug)]
+pub struct GetSeasonsResponse {
+    seasons: Vec<Season>,
+}
+
+#[get("/<secret_group_id>/seasons")]
+pub async fn get_seasons(
+    mut store: Connection<Store>,
+    group_key_config: &State<GroupKeyConfig>,
+    secret_group_id: String,
+) -> Result<Json<GetSeasonsResponse>, Error> {
+    let group_id = decode_and_validate_group_id(&group_key_config.group_key, secret_group_id)?;
+    skill_base::list_seasons(&mut store, &group_id)
+        .await
+        .map(|seasons| {
+            Json(GetSeasonsResponse {
+                seasons: seasons.into_iter().map(Season::from).collect(),
+            })
+        })
+}
+
+#[derive(Deserialize, Debug)]
+pub struct PostSeasonRequest {
+    name: Option<String>,
+    /// How far to move all skills toward the one of a new player, from 0 for
+    /// keeping them to 1 for starting over.
+    #[serde(default)]
+    reset: f64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct PostSeasonResponse {
+    season: Season,
+}
+
+/// Ends the running season, archives its leaderboard and starts a new one.
+#[post("/<secret_group_id>/seasons", data = "<request>")]
+pub async fn post_season(
+    mut store: Connection<Store>,
+    group_key_config: &State<GroupKeyConfig>,
+    secret_group_id: String,
+    request: Json<PostSeasonRequest>,
+) -> Result<Json<PostSeasonResponse>, Error> {
+    let group_id = decode_and_validate_group_id(&group_key_config.group_key, secret_group_id)?;
+    let request = request.into_inner();
+    skill_base::start_season(
+        &mut store,
+        &group_id,
+        request.name,
+        request.reset,
+        chrono::Utc::now(),
+    )
+    .await
+    .map(|season| {
+        Json(PostSeasonResponse {
+            season: season.into(),
+        })
+    })
+}
+
+/// Streams all events of a group as server-sent events until the client goes
+/// away or the server shuts down.
+#[get("/<secret_group_id>/events")]
+pub async fn get_events(
+    store: Connection<Store>,
+    group_key_config: &State<GroupKeyConfig>,
+    secret_group_id: String,
+    mut shutdown: Shutdown,
+) -> Result<EventStream![], Error> {
+    let group_id = decode_and_validate_group_id(&group_key_config.group_key, secret_group_id)?;
+    let mut events = Box::pin(skill_base::subscribe_events(store.into_inner(), &group_id).await?);
+    Ok(EventStream! {
+        loop {
+            let event = select! {
+                event = events.next() => match event {
+                    Some(event) => event,
+                    None => break,
+                },
+                _ = &mut shutdown => break,
+            };
+            yield Event::json(&event).event(event.name());
+        }
+    })
+}
+
+/// Represents a webhook without its secret.
+#[derive(Serialize, Debug)]
+struct Webhook {
+    id: WebhookId,
+    url: String,
+    events: Vec<String>,
+}
+
+impl From<webhook::Webhook> for Webhook {
+    fn from(webhook: webhook::Webhook) -> Self {
+        Webhook {
+            id: webhook.id().clone(),
+            url: webhook.url().to_owned(),
+            events: webhook.events().clone(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct PostWebhookRequest {
+    url: String,
+    secret: String,
+    /// Names of the events to deliver. All events are delivered if empty.
+    #[serde(default)]
+    events: Vec<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct PostWebhookResponse {
+    webhook: Webhook,
+}
+
+#[post("/<secret_group_id>/webhooks", data = "<request>")]
+pub async fn post_webhook(
+    mut store: Connection<Store>,
+    group_key_config: &State<GroupKeyConfig>,
+    secret_group_id: String,
+    request: Json<PostWebhookRequest>,
+) -> Result<Json<PostWebhookResponse>, Error> {
+    let group_id = decode_and_validate_group_id(&group_key_config.group_key, secret_group_id)?;
+    let request = request.into_inner();
+    let webhook = webhook::Webhook::new(
+        WebhookId::from(uuid::Uuid::new_v4().simple().to_string()),
+        request.url,
+        request.secret,
+        request.events,
+    );
+    skill_base::create_webhook(&mut store, &group_id, &webhook)
+        .await
+        .map(|()| {
+            Json(PostWebhookResponse {
+                webhook: webhook.into(),
+            })
+        })
+}
+
+#[derive(Serialize, Debug)]
+pub struct GetWebhooksResponse {
+    webhooks: Vec<Webhook>,
+}
+
+#[get("/<secret_group_id>/webhooks")]
+pub async fn get_webhooks(
+    mut store: Connection<Store>,
+    group_key_config: &State<GroupKeyConfig>,
+    secret_group_id: String,
+) -> Result<Json<GetWebhooksResponse>, Error> {
+    let group_id = decode_and_validate_group_id(&group_key_config.group_key, secret_group_id)?;
+    skill_base::list_webhooks(&mut store, &group_id)
+        .await
+        .map(|webhooks| {
+            Json(GetWebhooksResponse {
+                webhooks: webhooks.into_iter().map(Webhook::from).collect(),
+            })
+        })
+}
+
+#[delete("/<secret_group_id>/webhooks/<webhook_id>")]
+pub async fn delete_webhook(
+    mut store: Connection<Store>,
+    group_key_config: &State<GroupKeyConfig>,
+    secret_group_id: String,
+    webhook_id: WebhookId,
+) -> Result<Status, Error> {
+    let group_id = decode_and_validate_group_id(&group_key_config.group_key, secret_group_id)?;
+    skill_base::delete_webhook(&mut store, &group_id, &webhook_id)
+        .await
+        .map(|()| Status::NoContent)
+}
+
+#[derive(Serialize, Debug)]
+pub struct GetWebhookDeliveriesResponse {
+    deliveries: Vec<DeliveryStatus>,
+}
+
+#[get("/<secret_group_id>/webhooks/<webhook_id>/deliveries")]
+pub async fn get_webhook_deliveries(
+    mut store: Connection<Store>,
+    group_key_config: &State<GroupKeyConfig>,
+    secret_group_id: String,
+    webhook_id: WebhookId,
+) -> Result<Json<GetWebhookDeliveriesResponse>, Error> {
+    let group_id = decode_and_validate_group_id(&group_key_config.group_key, secret_group_id)?;
+    skill_base::list_webhook_deliveries(&mut store, &group_id, &webhook_id)
+        .await
+        .map(|deliveries| Json(GetWebhookDeliveriesResponse { deliveries }))
+}
+
+#[derive(Serialize, Debug)]
+pub struct GetHeadToHeadResponse {
+    user: User,
+    opponent: User,
+    /// Record of the user when playing against the opponent.
+    against: statistics::Record,
+    /// Record of the user when playing together with the opponent.
+    together: statistics::Record,
+    last_meetings: Vec<Game>,
+    /// Probability that the user wins a 1v1 game against the opponent.
+    win_probability: f64,
+}
+
+#[get("/<secret_group_id>/users/<user_id>/vs/<opponent_id>")]
+pub async fn get_head_to_head(
+    mut store: Connection<Store>,
+    group_key_config: &State<GroupKeyConfig>,
+    secret_group_id: String,
+    user_id: UserId,
+    opponent_id: UserId,
+) -> Result<Json<GetHeadToHeadResponse>, Error> {
+    let group_id = decode_and_validate_group_id(&group_key_config.group_key, secret_group_id)?;
+    let (user, opponent, head_to_head) = skill_base::get_head_to_head(
+        &mut store,
+        &group_id,
+        &user_id,
+        &opponent_id,
+        &chrono::Utc::now(),
+    )
+    .await?;
+
+    Ok(Json(GetHeadToHeadResponse {
+        user: user.into(),
+        opponent: opponent.into(),
+        against: head_to_head.against,
+        together: head_to_head.together,
+        last_meetings: head_to_head
+            .last_meetings
+            .into_iter()
+            .map(Game::from)
+            .collect(),
+        win_probability: head_to_head.win_probability,
+    }))
+}
+
+/// Number of partners and opponents listed per user.
+const PARTNERS_PER_USER: usize = 3;
+
+/// Reads the users with the given IDs, indexed by their ID.
+async fn read_user_map(
+    store: &mut Connection<Store>,
+    group_id: &skill_base::GroupId,
+    mut user_ids: Vec<UserId>,
+) -> Result<HashMap<UserId, skill_base::User>, Error> {
+    user_ids.sort();
+    user_ids.dedup();
+    let users = skill_base::read_users(store, group_id, &user_ids).await?;
+    Ok(user_ids.into_iter().zip(users).collect())
+}
+
+#[derive(Serialize, Debug)]
+struct Pair {
+    users: Vec<User>,
+    record: statistics::Record,
+    win_rate: f64,
+    /// Wins beyond the ones that were expected from the ratings.
+    performance: f64,
+}
+
+impl Pair {
+    fn new(pair: statistics::PairStatistics, users: &HashMap<UserId, skill_base::User>) -> Self {
+        Pair {
+            users: vec![
+                users[&pair.user_ids.0].clone().into(),
+                users[&pair.user_ids.1].clone().into(),
+            ],
+            record: pair.record,
+            win_rate: pair.win_rate(),
+            performance: pair.performance,
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+struct Opponent {
+    user: User,
+    record: statistics::Record,
+    /// Wins beyond the ones that were expected from the ratings.
+    performance: f64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct GetPairsResponse {
+    pairs: Vec<Pair>,
+}
+
+/// Lists the pairs that performed best when playing together. Only pairs with
+/// at least `min_games` games together are listed.
+#[get("/<secret_group_id>/pairs?<min_games>")]
+pub async fn get_pairs(
+    mut store: Connection<Store>,
+    group_key_config: &State<GroupKeyConfig>,
+    secret_group_id: String,
+    min_games: Option<usize>,
+) -> Result<Json<GetPairsResponse>, Error> {
+    let group_id = decode_and_validate_group_id(&group_key_config.group_key, secret_group_id)?;
+    let min_games = min_games.unwrap_or(3);
+    let mut pairs = skill_base::get_pair_statistics(&mut store, &group_id)
+        .await?
+        .into_iter()
+        .filter(|pair| pair.record.games() >= min_games)
+        .collect::<Vec<_>>();
+    pairs.sort_by(|pair_a, pair_b| pair_b.performance.total_cmp(&pair_a.performance));
+    pairs.truncate(100);
+
+    let users = read_user_map(
+        &mut store,
+        &group_id,
+        pairs
+            .iter()
+            .flat_map(|pair| vec![pair.user_ids.0.clone(), pair.user_ids.1.clone()])
+            .collect(),
+    )
+    .await?;
+    Ok(Json(GetPairsResponse {
+        pairs: pairs
+            .into_iter()
+            .map(|pair| Pair::new(pair, &users))
+            .collect(),
+    }))
+}
+
+#[derive(Serialize, Debug)]
+pub struct GetUserPartnersResponse {
+    user: User,
+    /// Partners with whom the user performed best.
+    best_partners: Vec<Pair>,
+    /// Opponents against whom the user performed worst.
+    nemeses: Vec<Opponent>,
+}
+
+#[get("/<secret_group_id>/users/<user_id>/partners")]
+pub async fn get_user_partners(
+    mut store: Connection<Store>,
+    group_key_config: &State<GroupKeyConfig>,
+    secret_group_id: String,
+    user_id: UserId,
+) -> Result<Json<GetUserPartnersResponse>, Error> {
+    let group_id = decode_and_validate_group_id(&group_key_config.group_key, secret_group_id)?;
+    let (mut pairs, mut opponents) =
+        skill_base::get_partner_statistics(&mut store, &group_id, &user_id).await?;
+    pairs.sort_by(|pair_a, pair_b| pair_b.performance.total_cmp(&pair_a.performance));
+    pairs.truncate(PARTNERS_PER_USER);
+    opponents.sort_by(|opponent_a, opponent_b| {
+        opponent_a.performance.total_cmp(&opponent_b.performance)
+    });
+    opponents.truncate(PARTNERS_PER_USER);
+
+    let mut user_ids = vec![user_id.clone()];
+    user_ids.extend(
+        pairs
+            .iter()
+            .flat_map(|pair| vec![pair.user_ids.0.clone(), pair.user_ids.1.clone()]),
+    );
+    user_ids.extend(
+        opponents
+            .iter()
+            .map(|opponent| opponent.opponent_id.clone()),
+    );
+    let users = read_user_map(&mut store, &group_id, user_ids).await?;
+
+    Ok(Json(GetUserPartnersResponse {
+        user: users[&user_id].clone().into(),
+        best_partners: pairs
+            .into_iter()
+            .map(|pair| Pair::new(pair, &users))
+            .collect(),
+        nemeses: opponents
+            .into_iter()
+            .map(|opponent| Opponent {
+                user: users[&opponent.opponent_id].clone().into(),
+                record: opponent.record,
+                performance: opponent.performance,
+            })
+            .collect(),
+    }))
+}
+
+#[derive(Serialize, Debug)]
+pub struct GetUserStatsResponse {
+    user: User,
+    record: statistics::Record,
+    current_win_streak: usize,
+    longest_win_streak: usize,
+    /// Highest score of the user. Only covers games with recorded skills.
+    peak: Option<statistics::Peak>,
+    games_last_30_days: usize,
+    frequent_opponents: Vec<Opponent>,
+}
+
+#[get("/<secret_group_id>/users/<user_id>/stats")]
+pub async fn get_user_stats(
+    mut store: Connection<Store>,
+    group_key_config: &State<GroupKeyConfig>,
+    secret_group_id: String,
+    user_id: UserId,
+) -> Result<Json<GetUserStatsResponse>, Error> {
+    let group_id = decode_and_validate_group_id(&group_key_config.group_key, secret_group_id)?;
+    let statistics =
+        skill_base::get_user_statistics(&mut store, &group_id, &user_id, &chrono::Utc::now())
+            .await?;
+
+    let mut user_ids = vec![user_id.clone()];
+    user_ids.extend(
+        statistics
+            .frequent_opponents
+            .iter()
+            .map(|opponent| opponent.opponent_id.clone()),
+    );
+    let users = read_user_map(&mut store, &group_id, user_ids).await?;
+
+    Ok(Json(GetUserStatsResponse {
+        user: users[&user_id].clone().into(),
+        record: statistics.record,
+        current_win_streak: statistics.current_win_streak,
+        longest_win_streak: statistics.longest_win_streak,
+        peak: statistics.peak,
+        games_last_30_days: statistics.recent_games,
+        frequent_opponents: statistics
+            .frequent_opponents
+            .into_iter()
+            .map(|opponent| Opponent {
+                user: users[&opponent.opponent_id].clone().into(),
+                record: opponent.record,
+                performance: opponent.performance,
+            })
+            .collect(),
+    }))
+}
+
+#[derive(Serialize, Debug)]
+struct SideAdvantage {
+    /// Table of the advantage, none for games without table.
+    table: Option<String>,
+    /// Performance the home side has over the away side.
+    advantage: Message,
+    games: usize,
+}
+
+impl From<&skill_base::SideAdvantage> for SideAdvantage {
+    fn from(side_advantage: &skill_base::SideAdvantage) -> Self {
+        SideAdvantage {
+            table: side_advantage.table().map(str::to_owned),
+            advantage: *side_advantage.advantage(),
+            games: side_advantage.games(),
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct GetGroupStatsResponse {
+    games: usize,
+    side_advantages: Vec<SideAdvantage>,
+}
+
+#[get("/<secret_group_id>/stats")]
+pub async fn get_group_stats(
+    mut store: Connection<Store>,
+    group_key_config: &State<GroupKeyConfig>,
+    secret_group_id: String,
+) -> Result<Json<GetGroupStatsResponse>, Error> {
+    let group_id = decode_and_validate_group_id(&group_key_config.group_key, secret_group_id)?;
+    let statistics = skill_base::get_group_statistics(&mut store, &group_id).await?;
+    Ok(Json(GetGroupStatsResponse {
+        games: statistics.games(),
+        side_advantages: statistics
+            .side_advantages()
+            .iter()
+            .map(SideAdvantage::from)
+            .collect(),
+    }))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct PostTournamentRequest {
+    name: String,
+    format: Format,
+    /// User IDs of the players of every entrant.
+    entrants: Vec<Vec<UserId>>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct TournamentResponse {
+    tournament: Tournament,
+}
+
+/// Creates a tournament and seeds its entrants by their current scores.
+#[post("/<secret_group_id>/tournaments", data = "<request>")]
+pub async fn post_tournament(
+    mut store: Connection<Store>,
+    group_key_config: &State<GroupKeyConfig>,
+    secret_group_id: String,
+    request: Json<PostTournamentRequest>,
+) -> Result<Json<TournamentResponse>, Error> {
+    let group_id = decode_and_validate_group_id(&group_key_config.group_key, secret_group_id)?;
+    let request = request.into_inner();
+    skill_base::create_tournament(
+        &mut store,
+        &group_id,
+        TournamentId::from(uuid::Uuid::new_v4().simple().to_string()),
+        request.name,
+        request.format,
+        request.entrants,
+        &chrono::Utc::now(),
+    )
+    .await
+    .map(|tournament| Json(TournamentResponse { tournament }))
+}
+
+#[derive(Serialize, Debug)]
+pub struct GetTournamentsResponse {
+    tournaments: Vec<Tournament>,
+}
+
+#[get("/<secret_group_id>/tournaments")]
+pub async fn get_tournaments(
+    mut store: Connection<Store>,
+    group_key_config: &State<GroupKeyConfig>,
+    secret_group_id: String,
+) -> Result<Json<GetTournamentsResponse>, Error> {
+    let group_id = decode_and_validate_group_id(&group_key_config.group_key, secret_group_id)?;
+    skill_base::list_tournaments(&mut store, &group_id)
+        .await
+        .map(|tournaments| Json(GetTournamentsResponse { tournaments }))
+}
+
+#[get("/<secret_group_id>/tournaments/<tournament_id>")]
+pub async fn get_tournament(
+    mut store: Connection<Store>,
+    group_key_config: &State<GroupKeyConfig>,
+    secret_group_id: String,
+    tournament_id: TournamentId,
+) -> Result<Json<TournamentResponse>, Error> {
+    let group_id = decode_and_validate_group_id(&group_key_config.group_key, secret_group_id)?;
+    skill_base::read_tournament(&mut store, &group_id, &tournament_id)
+        .await
+        .map(|tournament| Json(TournamentResponse { tournament }))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct PostTournamentMatchRequest {
+    /// Side of the match that won, 0 or 1.
+    winner: usize,
+}
+
+#[derive(Serialize, Debug)]
+pub struct PostTournamentMatchResponse {
+    tournament: Tournament,
+    game: Game,
+    /// Ratings of all players before and after the game.
+    rating_changes: Vec<skill_base::RatingChange>,
+}
+
+/// Records the result of a tournament match as a game.
+#[post(
+    "/<secret_group_id>/tournaments/<tournament_id>/matches/<match_index>",
+    data = "<request>"
+)]
+pub async fn post_tournament_match(
+    mut store: Connection<Store>,
+    group_key_config: &State<GroupKeyConfig>,
+    secret_group_id: String,
+    tournament_id: TournamentId,
+    match_index: usize,
+    request: Json<PostTournamentMatchRequest>,
+) -> Result<Json<PostTournamentMatchResponse>, Error> {
+    let group_id = decode_and_validate_group_id(&group_key_config.group_key, secret_group_id)?;
+    let (tournament, game, rating_changes) = skill_base::record_tournament_match(
+        &mut store,
+        &group_id,
+        &tournament_id,
+        match_index,
+        request.winner,
+        GameId::from(uuid::Uuid::new_v4().simple().to_string()),
+        chrono::Utc::now(),
+    )
+    .await?;
+    Ok(Json(PostTournamentMatchResponse {
+        tournament,
+        game: game.into(),
+        rating_changes,
+    }))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct PostLeagueRequest {
+    name: String,
+    /// User IDs of the players of every participant.
+    participants: Vec<Vec<UserId>>,
+    #[serde(default)]
+    scoring: Scoring,
+}
+
+#[derive(Serialize, Debug)]
+pub struct LeagueResponse {
+    league: League,
+    table: Vec<TableRow>,
+}
+
+impl From<League> for LeagueResponse {
+    fn from(league: League) -> Self {
+        LeagueResponse {
+            table: league.table(),
+            league,
+        }
+    }
+}
+
+/// Creates a round-robin league. Games between participants are assigned to
+/// their open fixtures as they get submitted.
+#[post("/<secret_group_id>/leagues", data = "<request>")]
+pub async fn post_league(
+    mut store: Connection<Store>,
+    group_key_config: &State<GroupKeyConfig>,
+    secret_group_id: String,
+    request: Json<PostLeagueRequest>,
+) -> Result<Json<LeagueResponse>, Error> {
+    let group_id = decode_and_validate_group_id(&group_key_config.group_key, secret_group_id)?;
+    let request = request.into_inner();
+    skill_base::create_league(
+        &mut store,
+        &group_id,
+        LeagueId::from(uuid::Uuid::new_v4().simple().to_string()),
+        request.name,
+        request.participants,
+        request.scoring,
+    )
+    .await
+    .map(|league| Json(league.into()))
+}
+
+#[derive(Serialize, Debug)]
+pub struct GetLeaguesResponse {
+    leagues: Vec<League>,
+}
+
+#[get("/<secret_group_id>/leagues")]
+pub async fn get_leagues(
+    mut store: Connection<Store>,
+    group_key_config: &State<GroupKeyConfig>,
+    secret_group_id: String,
+) -> Result<Json<GetLeaguesResponse>, Error> {
+    let group_id = decode_and_validate_group_id(&group_key_config.group_key, secret_group_id)?;
+    skill_base::list_leagues(&mut store, &group_id)
+        .await
+        .map(|leagues| Json(GetLeaguesResponse { leagues }))
+}
+
+/// Reads a league with its fixtures and its current table.
+#[get("/<secret_group_id>/leagues/<league_id>")]
+pub async fn get_league(
+    mut store: Connection<Store>,
+    group_key_config: &State<GroupKeyConfig>,
+    secret_group_id: String,
+    league_id: LeagueId,
+) -> Result<Json<LeagueResponse>, Error> {
+    let group_id = decode_and_validate_group_id(&group_key_config.group_key, secret_group_id)?;
+    skill_base::read_league(&mut store, &group_id, &league_id)
+        .await
+        .map(|league| Json(league.into()))
+}
+
+#[derive(Serialize, Debug)]
+pub struct GetQueueResponse {
+    queue: Vec<skill_base::QueueEntry>,
+}
+
+#[get("/<secret_group_id>/queue")]
+pub async fn get_queue(
+    mut store: Connection<Store>,
+    group_key_config: &State<GroupKeyConfig>,
+    secret_group_id: String,
+) -> Result<Json<GetQueueResponse>, Error> {
+    let group_id = decode_and_validate_group_id(&group_key_config.group_key, secret_group_id)?;
+    skill_base::list_queue(&mut store, &group_id)
+        .await
+        .map(|queue| Json(GetQueueResponse { queue }))
+}
+
+/// Marks a user as available for the next match.
+#[put("/<secret_group_id>/queue/<user_id>")]
+pub async fn put_queue_user(
+    mut store: Connection<Store>,
+    group_key_config: &State<GroupKeyConfig>,
+    secret_group_id: String,
+    user_id: UserId,
+) -> Result<Status, Error> {
+    let group_id = decode_and_validate_group_id(&group_key_config.group_key, secret_group_id)?;
+    skill_base::join_queue(&mut store, &group_id, &user_id, &chrono::Utc::now())
+        .await
+        .map(|()| Status::NoContent)
+}
+
+#[delete("/<secret_group_id>/queue/<user_id>")]
+pub async fn delete_queue_user(
+    mut store: Connection<Store>,
+    group_key_config: &State<GroupKeyConfig>,
+    secret_group_id: String,
+    user_id: UserId,
+) -> Result<Status, Error> {
+    let group_id = decode_and_validate_group_id(&group_key_config.group_key, secret_group_id)?;
+    skill_base::leave_queue(&mut store, &group_id, &user_id)
+        .await
+        .map(|()| Status::NoContent)
+}
+
+/// Proposes the fairest match between the users that waited longest and
+/// takes them out of the queue. Teams have two players unless `team_size`
+/// says otherwise.
+#[post("/<secret_group_id>/queue:next?<team_size>")]
+pub async fn post_queue_next(
+    mut store: Connection<Store>,
+    group_key_config: &State<GroupKeyConfig>,
+    secret_group_id: String,
+    team_size: Option<usize>,
+) -> Result<Json<Lineup>, Error> {
+    let group_id = decode_and_validate_group_id(&group_key_config.group_key, secret_group_id)?;
+    skill_base::next_match(
+        &mut store,
+        &group_id,
+        team_size.unwrap_or(2),
+        &chrono::Utc::now(),
+    )
+    .await
+    .map(Json)
+}
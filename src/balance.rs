@@ -0,0 +1,204 @@
+use serde::Serialize;
+
+use crate::message::Message;
+use crate::skill_base::{Position, UserId};
+
+/// Player in a proposed team.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct Slot {
+    pub user_id: UserId,
+    /// Position of the player. Only teams of two play in positions.
+    pub position: Option<Position>,
+}
+
+/// Proposed split of players into two teams.
+#[derive(Serialize, Clone, Debug)]
+pub struct Lineup {
+    pub teams: (Vec<Slot>, Vec<Slot>),
+    /// How fair the game is, higher is fairer.
+    pub quality: f64,
+    /// Probability that the first team wins.
+    pub win_probability: f64,
+}
+
+/// Lists all ways a team can line up.
+fn team_lineups(user_ids: &[&UserId]) -> Vec<Vec<Slot>> {
+    match *user_ids {
+        [first, second] => vec![
+            vec![
+                Slot {
+                    user_id: first.clone(),
+                    position: Some(Position::Offense),
+                },
+                Slot {
+                    user_id: second.clone(),
+                    position: Some(Position::Defense),
+                },
+            ],
+            vec![
+                Slot {
+                    user_id: first.clone(),
+                    position: Some(Position::Defense),
+                },
+                Slot {
+                    user_id: second.clone(),
+                    position: Some(Position::Offense),
+                },
+            ],
+        ],
+        _ => vec![user_ids
+            .iter()
+            .map(|user_id| Slot {
+                user_id: (*user_id).clone(),
+                position: None,
+            })
+            .collect()],
+    }
+}
+
+/// Splits players into two teams of equal size such that the game between
+/// them has the highest quality.
+///
+/// Returns `None` if the players cannot be split into two teams.
+///
+/// # Arguments
+///
+/// * `user_ids` IDs of all players.
+/// * `skill` returns the skill of a user in a position or across all positions.
+/// * `quality` returns how fair a game between two teams is.
+/// * `win_probability` returns the probability that the first team wins
+///   against the second.
+pub fn balance<S, Q, W>(
+    user_ids: &[UserId],
+    skill: S,
+    quality: Q,
+    win_probability: W,
+) -> Option<Lineup>
+where
+    S: Fn(&UserId, Option<Position>) -> Message,
+    Q: Fn(&[Message], &[Message]) -> f64,
+    W: Fn(&[Message], &[Message]) -> f64,
+{
+    if user_ids.is_empty() || !user_ids.len().is_multiple_of(2) {
+        return None;
+    }
+    let team_size = user_ids.len() / 2;
+    let skills = |team: &[Slot]| {
+        team.iter()
+            .map(|slot| skill(&slot.user_id, slot.position))
+            .collect::<Vec<_>>()
+    };
+
+    let mut best: Option<Lineup> = None;
+    // The first player always plays in the first team, which skips mirrored
+    // splits.
+    for split in 0..(1_u32 << (user_ids.len() - 1)) {
+        let split = (split << 1) | 1;
+        if split.count_ones() as usize != team_size {
+            continue;
+        }
+        let (first, second): (Vec<_>, Vec<_>) = user_ids
+            .iter()
+            .enumerate()
+            .partition(|(index, _)| split & (1 << *index) != 0);
+        let first = first.into_iter().map(|(_, id)| id).collect::<Vec<_>>();
+        let second = second.into_iter().map(|(_, id)| id).collect::<Vec<_>>();
+
+        for first_lineup in team_lineups(&first) {
+            for second_lineup in team_lineups(&second) {
+                let (first_skills, second_skills) = (skills(&first_lineup), skills(&second_lineup));
+                let lineup_quality = quality(&first_skills, &second_skills);
+                if best
+                    .as_ref()
+                    .is_none_or(|best| lineup_quality > best.quality)
+                {
+                    best = Some(Lineup {
+                        teams: (first_lineup.clone(), second_lineup),
+                        quality: lineup_quality,
+                        win_probability: win_probability(&first_skills, &second_skills),
+                    });
+                }
+            }
+        }
+    }
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(names: &[&str]) -> Vec<UserId> {
+        names
+            .iter()
+            .map(|name| UserId::from(name.to_string()))
+            .collect()
+    }
+
+    /// Compares teams by their summed mean skill.
+    fn win_probability(left: &[Message], right: &[Message]) -> f64 {
+        let mu = |team: &[Message]| team.iter().map(|m| m.to_mu_sigma2().0).sum::<f64>();
+        1.0 / (1.0 + (mu(right) - mu(left)).exp())
+    }
+
+    /// Prefers games that both teams are equally likely to win.
+    fn quality(left: &[Message], right: &[Message]) -> f64 {
+        1.0 - (win_probability(left, right) - 0.5).abs()
+    }
+
+    #[test]
+    fn test_balance_uneven() {
+        let skill = |_: &UserId, _| Message::from_mu_sigma2(25.0, 1.0);
+        assert!(balance(&ids(&[]), skill, quality, win_probability).is_none());
+        assert!(balance(&ids(&["a", "b", "c"]), skill, quality, win_probability).is_none());
+    }
+
+    #[test]
+    fn test_balance_teams() {
+        let skill = |user_id: &UserId, _| {
+            let mu = match user_id {
+                id if *id == UserId::from("a".to_string()) => 30.0,
+                id if *id == UserId::from("b".to_string()) => 28.0,
+                id if *id == UserId::from("c".to_string()) => 22.0,
+                _ => 20.0,
+            };
+            Message::from_mu_sigma2(mu, 1.0)
+        };
+        let lineup = balance(&ids(&["a", "b", "c", "d"]), skill, quality, win_probability).unwrap();
+        let user_ids = |team: &[Slot]| {
+            team.iter()
+                .map(|slot| slot.user_id.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(user_ids(&lineup.teams.0), ids(&["a", "d"]));
+        assert_eq!(user_ids(&lineup.teams.1), ids(&["b", "c"]));
+        assert!((lineup.win_probability - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_balance_positions() {
+        // "a" is a strong attacker and a weak goalie, everybody else is
+        // average.
+        let skill = |user_id: &UserId, position| {
+            let mu = match position {
+                Some(Position::Offense) if *user_id == UserId::from("a".to_string()) => 35.0,
+                Some(Position::Defense) if *user_id == UserId::from("a".to_string()) => 20.0,
+                _ => 25.0,
+            };
+            Message::from_mu_sigma2(mu, 1.0)
+        };
+        let lineup = balance(&ids(&["a", "b", "c", "d"]), skill, quality, win_probability).unwrap();
+        assert_eq!(lineup.teams.0[0].user_id, UserId::from("a".to_string()));
+        assert_eq!(lineup.teams.0[0].position, Some(Position::Defense));
+        assert_eq!(lineup.teams.0[1].position, Some(Position::Offense));
+        assert!(lineup.teams.1.iter().all(|slot| slot.position.is_some()));
+    }
+
+    #[test]
+    fn test_balance_singles() {
+        let skill = |_: &UserId, _| Message::from_mu_sigma2(25.0, 1.0);
+        let lineup = balance(&ids(&["a", "b"]), skill, quality, win_probability).unwrap();
+        assert_eq!(lineup.teams.0[0].position, None);
+        assert_eq!(lineup.teams.1[0].position, None);
+    }
+}
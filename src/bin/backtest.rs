@@ -0,0 +1,77 @@
+use std::env;
+
+use fooskill::evaluation::{self, Parameters, Report};
+use fooskill::snapshot::Snapshot;
+
+/// Factors applied to the default beta.
+const BETA_FACTORS: [f64; 4] = [0.5, 1.0, 1.5, 2.0];
+/// Draw margins in units of skill.
+const DRAW_MARGINS: [f64; 3] = [0.0, 0.1, 0.5];
+/// Factors applied to the default speed at which sigma2 increases.
+const SIGMA2_CHANGE_SPEED_FACTORS: [f64; 6] = [0.0, 0.25, 0.5, 1.0, 2.0, 4.0];
+
+/// Number of best parameter combinations to print.
+const TOP_REPORTS: usize = 10;
+
+fn print_report(report: &Report) {
+    println!(
+        "beta {:>7.3}  draw margin {:>5.2}  sigma2 change speed {:>10.3e}  log-loss {:.4}  accuracy {:.4}",
+        report.parameters.beta,
+        report.parameters.draw_margin,
+        report.parameters.sigma2_change_speed,
+        report.log_loss,
+        report.accuracy,
+    );
+}
+
+fn print_calibration(report: &Report) {
+    println!("predicted range  count  predicted  observed");
+    for bin in &report.calibration {
+        println!(
+            "{:.1} - {:.1}        {:>5}  {:>9.3}  {:>8.3}",
+            bin.lower, bin.upper, bin.count, bin.predicted, bin.observed
+        );
+    }
+}
+
+fn go() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Vec<String> = env::args().collect();
+
+    let input_path = &args[1];
+
+    let mut snapshot = Snapshot::read(input_path)?;
+    snapshot.games.sort_by_key(|game| game.timestamp);
+    let games = snapshot
+        .games
+        .into_iter()
+        .map(|game| game.into_game())
+        .collect::<Vec<_>>();
+
+    let defaults = Parameters::default();
+    let current = evaluation::evaluate(&games, defaults);
+    println!("Current parameters on {} games:", current.games);
+    print_report(&current);
+    print_calibration(&current);
+
+    let reports = evaluation::grid_search(
+        &games,
+        &BETA_FACTORS.map(|factor| factor * defaults.beta),
+        &DRAW_MARGINS,
+        &SIGMA2_CHANGE_SPEED_FACTORS.map(|factor| factor * defaults.sigma2_change_speed),
+    );
+    println!();
+    println!("Best of {} parameter combinations:", reports.len());
+    for report in reports.iter().take(TOP_REPORTS) {
+        print_report(report);
+    }
+    if let Some(best) = reports.first() {
+        println!();
+        print_calibration(best);
+    }
+
+    Ok(())
+}
+
+fn main() {
+    go().unwrap();
+}
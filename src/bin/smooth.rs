@@ -0,0 +1,35 @@
+use std::env;
+
+use rocket::tokio;
+use rocket_db_pools::deadpool_redis::{Config, Runtime};
+
+use fooskill::skill_base;
+
+async fn go() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Vec<String> = env::args().collect();
+
+    let secret_group_id = &args[1];
+    let group_key = &args[2];
+
+    let cfg = Config::from_url("redis://127.0.0.1/");
+    let pool = cfg.create_pool(Some(Runtime::Tokio1)).unwrap();
+
+    let mut connection = pool.get().await?;
+
+    let group_key = skill_base::GroupKey::new(group_key.to_owned()).unwrap();
+    let group_id = skill_base::decode_and_validate_group_id(
+        &group_key,
+        percent_encoding::percent_decode_str(secret_group_id)
+            .decode_utf8()?
+            .into_owned(),
+    )?;
+
+    skill_base::smooth_ratings(&mut connection, &group_id, chrono::Utc::now()).await?;
+
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() {
+    go().await.unwrap();
+}
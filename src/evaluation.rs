@@ -0,0 +1,288 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::message::Message;
+use crate::player::Player;
+use crate::skill_base::{Game, UserId};
+use crate::true_skill::TrueSkill;
+
+/// Number of equally wide bins of predicted probabilities for calibration.
+const CALIBRATION_BINS: usize = 10;
+
+/// Smallest probability a prediction counts with, so that a single confident
+/// miss does not make the log-loss infinite.
+const MIN_PROBABILITY: f64 = 1e-15;
+
+/// Tunable parameters of the rating model.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+pub struct Parameters {
+    /// Standard deviation of the game performance around the skill.
+    pub beta: f64,
+    /// Margin of performance difference within which a game is a draw.
+    pub draw_margin: f64,
+    /// Speed at which sigma2 increases per second between games.
+    pub sigma2_change_speed: f64,
+}
+
+impl Default for Parameters {
+    /// Parameters the ratings of all groups use.
+    fn default() -> Self {
+        Parameters {
+            beta: Player::default_sigma() / 2.0,
+            draw_margin: 0.0,
+            sigma2_change_speed: Player::default_sigma2_change_speed(),
+        }
+    }
+}
+
+/// Predictions within a range of probabilities.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct CalibrationBin {
+    pub lower: f64,
+    pub upper: f64,
+    /// Number of predictions in the bin.
+    pub count: usize,
+    /// Mean predicted probability.
+    pub predicted: f64,
+    /// Fraction of the predictions that came true.
+    pub observed: f64,
+}
+
+/// How well a rating model predicted a game history.
+#[derive(Serialize, Clone, Debug)]
+pub struct Report {
+    pub parameters: Parameters,
+    /// Number of predicted games.
+    pub games: usize,
+    /// Mean negative log-probability of the actual winners winning, given
+    /// that the game was not a draw.
+    pub log_loss: f64,
+    /// Fraction of games the favoured team won. Games without favourite
+    /// count half.
+    pub accuracy: f64,
+    /// Predicted probabilities of both teams winning against how often they
+    /// did.
+    pub calibration: Vec<CalibrationBin>,
+}
+
+/// Returns the skills of users at a point in time. Users that have not
+/// played yet start out as new players.
+fn skills_at<'a>(
+    players: &mut HashMap<&'a UserId, Player>,
+    user_ids: &'a [UserId],
+    datetime: &chrono::DateTime<chrono::Utc>,
+    parameters: &Parameters,
+) -> Vec<Message> {
+    user_ids
+        .iter()
+        .map(|user_id| {
+            let player = players
+                .entry(user_id)
+                .or_insert_with(|| Player::new(*datetime));
+            // Games out of order count with the latest skill.
+            player
+                .skill_at_with_change_speed(datetime, parameters.sigma2_change_speed)
+                .or_else(|| player.skill_at(player.datetime()))
+                .unwrap()
+        })
+        .collect()
+}
+
+/// Replays a game history and predicts the outcome of every game with the
+/// ratings from all games before it.
+///
+/// # Arguments
+///
+/// * `games` all games from the oldest to the latest.
+/// * `parameters` parameters of the rating model.
+pub fn evaluate(games: &[Game], parameters: Parameters) -> Report {
+    let true_skill = TrueSkill::new(parameters.beta, parameters.draw_margin);
+    let mut players: HashMap<&UserId, Player> = HashMap::new();
+
+    let mut log_loss = 0.0;
+    let mut correct = 0.0;
+    // Sums of predicted probabilities and outcomes, and counts per bin.
+    let mut bins = vec![(0.0, 0.0, 0); CALIBRATION_BINS];
+    for game in games {
+        let datetime = *game.datetime();
+        let winner_skills = skills_at(&mut players, game.winner_ids(), &datetime, &parameters);
+        let loser_skills = skills_at(&mut players, game.loser_ids(), &datetime, &parameters);
+
+        // Histories have no draws, so the prediction is conditioned on the
+        // game having a winner.
+        let win_probability = true_skill.win_probability(&winner_skills, &loser_skills);
+        let loss_probability = true_skill.win_probability(&loser_skills, &winner_skills);
+        let decisive_probability = win_probability + loss_probability;
+        let (win_probability, loss_probability) = (
+            win_probability / decisive_probability,
+            loss_probability / decisive_probability,
+        );
+        log_loss -= win_probability.max(MIN_PROBABILITY).ln();
+        if win_probability > loss_probability {
+            correct += 1.0;
+        } else if win_probability == loss_probability {
+            correct += 0.5;
+        }
+        for (probability, outcome) in [(win_probability, 1.0), (loss_probability, 0.0)] {
+            let bin = &mut bins
+                [((probability * CALIBRATION_BINS as f64) as usize).min(CALIBRATION_BINS - 1)];
+            bin.0 += probability;
+            bin.1 += outcome;
+            bin.2 += 1;
+        }
+
+        let weighted = |user_ids: &[UserId], skills: &[Message]| {
+            user_ids
+                .iter()
+                .zip(skills)
+                .map(|(user_id, skill)| (game.weight(user_id), *skill))
+                .collect::<Vec<_>>()
+        };
+        let updates = true_skill.rank_pass(
+            &[
+                weighted(game.winner_ids(), &winner_skills),
+                weighted(game.loser_ids(), &loser_skills),
+            ],
+            &[0, 1],
+        );
+        for ((user_id, skill), update) in game
+            .winner_ids()
+            .iter()
+            .chain(game.loser_ids())
+            .zip(winner_skills.iter().chain(&loser_skills))
+            .zip(updates.iter().flatten())
+        {
+            if let Some(player) = players.get_mut(user_id) {
+                player.set_skill(skill.include(update), datetime);
+            }
+        }
+    }
+
+    let count = games.len().max(1) as f64;
+    Report {
+        parameters,
+        games: games.len(),
+        log_loss: log_loss / count,
+        accuracy: correct / count,
+        calibration: bins
+            .into_iter()
+            .enumerate()
+            .map(|(index, (predicted, observed, count))| CalibrationBin {
+                lower: index as f64 / CALIBRATION_BINS as f64,
+                upper: (index + 1) as f64 / CALIBRATION_BINS as f64,
+                count,
+                predicted: predicted / count.max(1) as f64,
+                observed: observed / count.max(1) as f64,
+            })
+            .collect(),
+    }
+}
+
+/// Evaluates every combination of the given parameter values and returns the
+/// reports from the lowest to the highest log-loss.
+///
+/// # Arguments
+///
+/// * `games` all games from the oldest to the latest.
+/// * `betas` values for `Parameters::beta`.
+/// * `draw_margins` values for `Parameters::draw_margin`.
+/// * `sigma2_change_speeds` values for `Parameters::sigma2_change_speed`.
+pub fn grid_search(
+    games: &[Game],
+    betas: &[f64],
+    draw_margins: &[f64],
+    sigma2_change_speeds: &[f64],
+) -> Vec<Report> {
+    let mut reports = Vec::new();
+    for beta in betas {
+        for draw_margin in draw_margins {
+            for sigma2_change_speed in sigma2_change_speeds {
+                reports.push(evaluate(
+                    games,
+                    Parameters {
+                        beta: *beta,
+                        draw_margin: *draw_margin,
+                        sigma2_change_speed: *sigma2_change_speed,
+                    },
+                ));
+            }
+        }
+    }
+    reports.sort_by(|a, b| {
+        a.log_loss
+            .partial_cmp(&b.log_loss)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    reports
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::skill_base::GameId;
+
+    fn games(results: &[(&str, &str)]) -> Vec<Game> {
+        let start = chrono::Utc::now() - chrono::Duration::days(results.len() as i64);
+        results
+            .iter()
+            .enumerate()
+            .map(|(index, (winner, loser))| {
+                Game::new(
+                    GameId::from(index.to_string()),
+                    start + chrono::Duration::days(index as i64),
+                    vec![UserId::from(winner.to_string())],
+                    vec![UserId::from(loser.to_string())],
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let report = evaluate(&games(&[("a", "b"); 10]), Parameters::default());
+        assert_eq!(report.games, 10);
+        // The first game is a coin flip, all later ones favour "a".
+        assert!((report.accuracy - 0.95).abs() < 1e-9);
+        assert!(report.log_loss < 2_f64.ln());
+        assert_eq!(
+            report
+                .calibration
+                .iter()
+                .map(|bin| bin.count)
+                .sum::<usize>(),
+            20
+        );
+        assert_eq!(report.calibration[5].count, 2);
+    }
+
+    #[test]
+    fn test_evaluate_draw_margin() {
+        // Chances of a draw do not count against the prediction.
+        let parameters = Parameters {
+            draw_margin: 10.0,
+            ..Parameters::default()
+        };
+        let report = evaluate(&games(&[("a", "b")]), parameters);
+        assert!((report.log_loss - 2_f64.ln()).abs() < 1e-9);
+        assert!((report.calibration[5].predicted - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_evaluate_no_games() {
+        let report = evaluate(&[], Parameters::default());
+        assert_eq!(report.games, 0);
+        assert_eq!(report.log_loss, 0.0);
+    }
+
+    #[test]
+    fn test_grid_search() {
+        let games = games(&[("a", "b"), ("b", "c"), ("a", "c"), ("a", "b")]);
+        let reports = grid_search(&games, &[2.0, 4.0], &[0.0], &[0.0, 1e-6]);
+        assert_eq!(reports.len(), 4);
+        assert!(reports
+            .windows(2)
+            .all(|pair| pair[0].log_loss <= pair[1].log_loss));
+    }
+}
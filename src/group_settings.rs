@@ -0,0 +1,400 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::rating_system::RatingSystemKind;
+use crate::skill_base::UserId;
+
+/// Settings that can be configured per group.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct GroupSettings {
+    /// Rules every submitted game has to follow.
+    #[serde(default)]
+    pub game_rules: GameRules,
+    /// Kinds of games that are rated separately. Games that fit none of them,
+    /// e.g. all games if there are none, only have overall ratings.
+    #[serde(default)]
+    pub game_modes: Vec<GameMode>,
+    /// Rating systems that are computed next to TrueSkill for comparison.
+    #[serde(default)]
+    pub rating_systems: Vec<RatingSystemKind>,
+}
+
+/// Kind of game that has its own ratings, e.g. singles or doubles.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct GameMode {
+    pub name: String,
+    /// Number of players per team.
+    pub team_size: usize,
+}
+
+impl GroupSettings {
+    /// Finds the mode of a game with the given teams. Returns `None` if no game
+    /// mode fits the teams, so that the game is only rated overall.
+    pub fn game_mode(&self, winner_ids: &[UserId], loser_ids: &[UserId]) -> Option<&GameMode> {
+        self.game_modes
+            .iter()
+            .find(|mode| mode.team_size == winner_ids.len() && mode.team_size == loser_ids.len())
+    }
+
+    /// Checks that the game modes have unique names and team sizes that the
+    /// game rules allow.
+    pub fn validate(&self) -> Result<(), SettingsError> {
+        let mut names = HashSet::new();
+        for mode in &self.game_modes {
+            if !names.insert(&mode.name) {
+                return Err(SettingsError::DuplicateGameMode {
+                    name: mode.name.clone(),
+                });
+            }
+            if mode.team_size == 0 {
+                return Err(SettingsError::EmptyGameMode {
+                    name: mode.name.clone(),
+                });
+            }
+            let allowed = &self.game_rules.allowed_team_sizes;
+            if !allowed.is_empty() && !allowed.contains(&mode.team_size) {
+                return Err(SettingsError::GameModeTeamSizeNotAllowed {
+                    name: mode.name.clone(),
+                    team_size: mode.team_size,
+                    allowed: allowed.clone(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the group has a game mode with this name.
+    pub fn has_game_mode(&self, name: &str) -> bool {
+        self.game_modes.iter().any(|mode| mode.name == name)
+    }
+}
+
+/// Optional rules for games in a group.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct GameRules {
+    /// Both teams need to have the same number of players.
+    #[serde(default)]
+    pub equal_team_sizes: bool,
+    /// Team sizes that are allowed. All team sizes are allowed if this is
+    /// empty.
+    #[serde(default)]
+    pub allowed_team_sizes: Vec<usize>,
+}
+
+/// Names one of the two teams of a game.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Team {
+    Winners,
+    Losers,
+}
+
+/// Describes the rule a submitted game violates.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+#[serde(tag = "rule", rename_all = "snake_case")]
+pub enum GameError {
+    /// A team has no players.
+    EmptyTeam { team: Team },
+    /// A user shows up more than once in a game.
+    DuplicatePlayer { user_id: UserId },
+    /// A user in the game does not exist.
+    UnknownUser { user_id: UserId },
+    /// The game took place before the last recorded game of a user.
+    BeforeLastGame { user_id: UserId },
+    /// A game of a batch took place before `timestamp`, in milliseconds since
+    /// the Unix epoch, up to which the skill of a user is already recorded.
+    /// Batches can only add games after that point in time.
+    BackdatedGame { user_id: UserId, timestamp: i64 },
+    /// The point in time of the game is out of range or in the future.
+    InvalidTimestamp,
+    /// The teams have different sizes, but the group requires equal sizes.
+    UnequalTeamSizes { winners: usize, losers: usize },
+    /// The group does not allow teams of this size.
+    TeamSizeNotAllowed { size: usize, allowed: Vec<usize> },
+    /// Some players have a position, but this one has none.
+    MissingPosition { user_id: UserId },
+    /// A user that does not play in the game has a position.
+    PositionOfNonPlayer { user_id: UserId },
+    /// Only teams of two players have positions.
+    PositionsNotAllowed { team: Team, size: usize },
+    /// A team of two needs one offense and one defense player.
+    InvalidPositions { team: Team },
+    /// The fraction of the game a user played is not above 0 and at most 1.
+    InvalidWeight { user_id: UserId },
+    /// A user that does not play in the game has a fraction of the game.
+    WeightOfNonPlayer { user_id: UserId },
+    /// The game has a table, but not the side of the winners.
+    TableWithoutSide,
+}
+
+impl fmt::Display for GameError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            GameError::EmptyTeam { team } => write!(f, "{:?} team has no players", team),
+            GameError::DuplicatePlayer { ref user_id } => {
+                write!(f, "user {:?} plays more than once", user_id)
+            }
+            GameError::UnknownUser { ref user_id } => {
+                write!(f, "user {:?} does not exist", user_id)
+            }
+            GameError::BeforeLastGame { ref user_id } => {
+                write!(f, "user {:?} already played a later game", user_id)
+            }
+            GameError::BackdatedGame {
+                ref user_id,
+                timestamp,
+            } => write!(
+                f,
+                "user {:?} has a skill recorded at {} after this game, games cannot be inserted before it",
+                user_id, timestamp
+            ),
+            GameError::InvalidTimestamp => write!(f, "timestamp is out of range or in the future"),
+            GameError::UnequalTeamSizes { winners, losers } => write!(
+                f,
+                "teams must have the same size, but have {} and {} players",
+                winners, losers
+            ),
+            GameError::TeamSizeNotAllowed { size, ref allowed } => write!(
+                f,
+                "teams with {} players are not allowed, only {:?}",
+                size, allowed
+            ),
+            GameError::MissingPosition { ref user_id } => {
+                write!(f, "user {:?} has no position", user_id)
+            }
+            GameError::PositionOfNonPlayer { ref user_id } => {
+                write!(f, "user {:?} has a position but does not play", user_id)
+            }
+            GameError::PositionsNotAllowed { team, size } => write!(
+                f,
+                "{:?} team has {} players, but only teams of two have positions",
+                team, size
+            ),
+            GameError::InvalidPositions { team } => write!(
+                f,
+                "{:?} team needs one offense and one defense player",
+                team
+            ),
+            GameError::InvalidWeight { ref user_id } => write!(
+                f,
+                "user {:?} has to play more than none and at most all of the game",
+                user_id
+            ),
+            GameError::WeightOfNonPlayer { ref user_id } => {
+                write!(
+                    f,
+                    "user {:?} has a fraction of the game but does not play",
+                    user_id
+                )
+            }
+            GameError::TableWithoutSide => {
+                write!(
+                    f,
+                    "table is only known together with the side of the winners"
+                )
+            }
+        }
+    }
+}
+
+/// Describes the rule group settings violate.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+#[serde(tag = "rule", rename_all = "snake_case")]
+pub enum SettingsError {
+    /// Two game modes have the same name.
+    DuplicateGameMode { name: String },
+    /// A game mode has teams without players.
+    EmptyGameMode { name: String },
+    /// The game rules do not allow the team size of a game mode.
+    GameModeTeamSizeNotAllowed {
+        name: String,
+        team_size: usize,
+        allowed: Vec<usize>,
+    },
+}
+
+impl fmt::Display for SettingsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SettingsError::DuplicateGameMode { ref name } => {
+                write!(f, "game mode {:?} exists more than once", name)
+            }
+            SettingsError::EmptyGameMode { ref name } => {
+                write!(f, "game mode {:?} has teams without players", name)
+            }
+            SettingsError::GameModeTeamSizeNotAllowed {
+                ref name,
+                team_size,
+                ref allowed,
+            } => write!(
+                f,
+                "game mode {:?} has teams with {} players, but only {:?} are allowed",
+                name, team_size, allowed
+            ),
+        }
+    }
+}
+
+impl GameRules {
+    /// Checks that a game with the given teams is valid and follows these
+    /// rules.
+    pub fn validate(&self, winner_ids: &[UserId], loser_ids: &[UserId]) -> Result<(), GameError> {
+        for (team, ids) in [(Team::Winners, winner_ids), (Team::Losers, loser_ids)] {
+            if ids.is_empty() {
+                return Err(GameError::EmptyTeam { team });
+            }
+        }
+
+        let mut seen = HashSet::new();
+        for user_id in winner_ids.iter().chain(loser_ids) {
+            if !seen.insert(user_id) {
+                return Err(GameError::DuplicatePlayer {
+                    user_id: user_id.clone(),
+                });
+            }
+        }
+
+        if self.equal_team_sizes && winner_ids.len() != loser_ids.len() {
+            return Err(GameError::UnequalTeamSizes {
+                winners: winner_ids.len(),
+                losers: loser_ids.len(),
+            });
+        }
+
+        if !self.allowed_team_sizes.is_empty() {
+            for size in [winner_ids.len(), loser_ids.len()] {
+                if !self.allowed_team_sizes.contains(&size) {
+                    return Err(GameError::TeamSizeNotAllowed {
+                        size,
+                        allowed: self.allowed_team_sizes.clone(),
+                    });
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(names: &[&str]) -> Vec<UserId> {
+        names
+            .iter()
+            .map(|name| UserId::from(name.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_validate_default() {
+        let rules = GameRules::default();
+        assert_eq!(rules.validate(&ids(&["a"]), &ids(&["b", "c"])), Ok(()));
+        assert_eq!(
+            rules.validate(&ids(&["a"]), &ids(&[])),
+            Err(GameError::EmptyTeam { team: Team::Losers })
+        );
+        assert_eq!(
+            rules.validate(&ids(&["a", "a"]), &ids(&["b"])),
+            Err(GameError::DuplicatePlayer {
+                user_id: UserId::from("a".to_string())
+            })
+        );
+        assert_eq!(
+            rules.validate(&ids(&["a", "b"]), &ids(&["b"])),
+            Err(GameError::DuplicatePlayer {
+                user_id: UserId::from("b".to_string())
+            })
+        );
+    }
+
+    #[test]
+    fn test_validate_rules() {
+        let rules = GameRules {
+            equal_team_sizes: true,
+            allowed_team_sizes: vec![1, 2],
+        };
+        assert_eq!(rules.validate(&ids(&["a", "b"]), &ids(&["c", "d"])), Ok(()));
+        assert_eq!(
+            rules.validate(&ids(&["a"]), &ids(&["b", "c"])),
+            Err(GameError::UnequalTeamSizes {
+                winners: 1,
+                losers: 2
+            })
+        );
+        assert_eq!(
+            rules.validate(&ids(&["a", "b", "c"]), &ids(&["d", "e", "f"])),
+            Err(GameError::TeamSizeNotAllowed {
+                size: 3,
+                allowed: vec![1, 2]
+            })
+        );
+    }
+
+    #[test]
+    fn test_game_mode() {
+        let mut settings = GroupSettings::default();
+        assert_eq!(settings.game_mode(&ids(&["a"]), &ids(&["b", "c"])), None);
+
+        settings.game_modes = vec![
+            GameMode {
+                name: "singles".to_owned(),
+                team_size: 1,
+            },
+            GameMode {
+                name: "doubles".to_owned(),
+                team_size: 2,
+            },
+        ];
+        assert_eq!(
+            settings
+                .game_mode(&ids(&["a", "b"]), &ids(&["c", "d"]))
+                .map(|mode| mode.name.as_str()),
+            Some("doubles")
+        );
+        assert_eq!(settings.game_mode(&ids(&["a"]), &ids(&["b", "c"])), None);
+    }
+
+    #[test]
+    fn test_validate_settings() {
+        let mode = |name: &str, team_size| GameMode {
+            name: name.to_owned(),
+            team_size,
+        };
+        let mut settings = GroupSettings {
+            game_modes: vec![mode("singles", 1), mode("doubles", 2)],
+            ..GroupSettings::default()
+        };
+        assert_eq!(settings.validate(), Ok(()));
+
+        settings.game_rules.allowed_team_sizes = vec![2];
+        assert_eq!(
+            settings.validate(),
+            Err(SettingsError::GameModeTeamSizeNotAllowed {
+                name: "singles".to_owned(),
+                team_size: 1,
+                allowed: vec![2]
+            })
+        );
+
+        settings.game_rules.allowed_team_sizes = vec![];
+        settings.game_modes.push(mode("singles", 1));
+        assert_eq!(
+            settings.validate(),
+            Err(SettingsError::DuplicateGameMode {
+                name: "singles".to_owned()
+            })
+        );
+
+        settings.game_modes = vec![mode("none", 0)];
+        assert_eq!(
+            settings.validate(),
+            Err(SettingsError::EmptyGameMode {
+                name: "none".to_owned()
+            })
+        );
+    }
+}
@@ -0,0 +1,395 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::skill_base::{GameId, LeagueId, UserId};
+
+/// Minimal number of participants of a league.
+pub const MIN_PARTICIPANTS: usize = 2;
+
+/// Points a participant gets for the result of a fixture.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Scoring {
+    pub win: u32,
+    pub loss: u32,
+}
+
+impl Default for Scoring {
+    fn default() -> Self {
+        Scoring { win: 3, loss: 0 }
+    }
+}
+
+/// Scheduled game between two participants of a league.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Fixture {
+    /// Round of the schedule starting at 1.
+    pub round: usize,
+    /// Indices of the two participants.
+    pub participants: [usize; 2],
+    /// Game that was played for the fixture.
+    pub game_id: Option<GameId>,
+    /// Index of the participant that won.
+    pub winner: Option<usize>,
+}
+
+/// Row of a league table.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct TableRow {
+    /// Index of the participant.
+    pub participant: usize,
+    pub played: u32,
+    pub wins: u32,
+    pub losses: u32,
+    pub points: u32,
+}
+
+/// Describes why a league cannot be created.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+#[serde(tag = "rule", rename_all = "snake_case")]
+pub enum LeagueError {
+    /// The league has fewer than `min_participants` participants.
+    TooFewParticipants { min_participants: usize },
+    /// A participant has no players.
+    EmptyParticipant { participant: usize },
+    /// A user plays for more than one participant or more than once.
+    DuplicatePlayer { user_id: UserId },
+}
+
+impl fmt::Display for LeagueError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LeagueError::TooFewParticipants { min_participants } => {
+                write!(f, "league needs at least {} participants", min_participants)
+            }
+            LeagueError::EmptyParticipant { participant } => {
+                write!(f, "participant {} has no players", participant)
+            }
+            LeagueError::DuplicatePlayer { ref user_id } => {
+                write!(f, "user {:?} plays more than once", user_id)
+            }
+        }
+    }
+}
+
+/// Round-robin league in which every participant plays every other
+/// participant once.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct League {
+    id: LeagueId,
+    name: String,
+    /// Players of all participants.
+    participants: Vec<Vec<UserId>>,
+    scoring: Scoring,
+    fixtures: Vec<Fixture>,
+}
+
+impl League {
+    /// Creates a league and schedules all its fixtures.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` ID of the league.
+    /// * `name` name of the league.
+    /// * `participants` players of all participants, which are single
+    ///   players or teams.
+    /// * `scoring` points for wins and losses.
+    pub fn new(
+        id: LeagueId,
+        name: String,
+        participants: Vec<Vec<UserId>>,
+        scoring: Scoring,
+    ) -> Result<Self, LeagueError> {
+        if participants.len() < MIN_PARTICIPANTS {
+            return Err(LeagueError::TooFewParticipants {
+                min_participants: MIN_PARTICIPANTS,
+            });
+        }
+        if let Some(participant) = participants.iter().position(|user_ids| user_ids.is_empty()) {
+            return Err(LeagueError::EmptyParticipant { participant });
+        }
+        let mut seen = HashSet::new();
+        if let Some(user_id) = participants
+            .iter()
+            .flatten()
+            .find(|user_id| !seen.insert(*user_id))
+        {
+            return Err(LeagueError::DuplicatePlayer {
+                user_id: user_id.clone(),
+            });
+        }
+
+        let fixtures = schedule(participants.len())
+            .into_iter()
+            .enumerate()
+            .flat_map(|(round, pairings)| {
+                pairings.into_iter().map(move |participants| Fixture {
+                    round: round + 1,
+                    participants,
+                    game_id: None,
+                    winner: None,
+                })
+            })
+            .collect();
+        Ok(League {
+            id,
+            name,
+            participants,
+            scoring,
+            fixtures,
+        })
+    }
+
+    pub fn id(&self) -> &LeagueId {
+        &self.id
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn participants(&self) -> &Vec<Vec<UserId>> {
+        &self.participants
+    }
+
+    pub fn fixtures(&self) -> &Vec<Fixture> {
+        &self.fixtures
+    }
+
+    /// Whether all fixtures were played.
+    pub fn is_finished(&self) -> bool {
+        self.fixtures
+            .iter()
+            .all(|fixture| fixture.game_id.is_some())
+    }
+
+    /// Assigns a game to the earliest open fixture between its two teams.
+    /// Returns whether the game belongs to a fixture.
+    ///
+    /// # Arguments
+    ///
+    /// * `game_id` ID of the game.
+    /// * `winner_ids` user IDs of winning users.
+    /// * `loser_ids` user IDs of losing users.
+    pub fn record(
+        &mut self,
+        game_id: &GameId,
+        winner_ids: &[UserId],
+        loser_ids: &[UserId],
+    ) -> bool {
+        let (winner, loser) = match (self.find(winner_ids), self.find(loser_ids)) {
+            (Some(winner), Some(loser)) => (winner, loser),
+            _ => return false,
+        };
+        let fixture = self.fixtures.iter_mut().find(|fixture| {
+            fixture.game_id.is_none()
+                && (fixture.participants == [winner, loser]
+                    || fixture.participants == [loser, winner])
+        });
+        match fixture {
+            Some(fixture) => {
+                fixture.game_id = Some(game_id.clone());
+                fixture.winner = Some(winner);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Ranks all participants by their points, then by their wins.
+    pub fn table(&self) -> Vec<TableRow> {
+        let mut rows = (0..self.participants.len())
+            .map(|participant| TableRow {
+                participant,
+                played: 0,
+                wins: 0,
+                losses: 0,
+                points: 0,
+            })
+            .collect::<Vec<_>>();
+        for fixture in &self.fixtures {
+            let winner = match fixture.winner {
+                Some(winner) => winner,
+                None => continue,
+            };
+            for participant in fixture.participants.iter() {
+                let row = &mut rows[*participant];
+                row.played += 1;
+                if *participant == winner {
+                    row.wins += 1;
+                    row.points += self.scoring.win;
+                } else {
+                    row.losses += 1;
+                    row.points += self.scoring.loss;
+                }
+            }
+        }
+        rows.sort_by(|a, b| b.points.cmp(&a.points).then(b.wins.cmp(&a.wins)));
+        rows
+    }
+
+    /// Finds the participant that consists of exactly these users.
+    fn find(&self, user_ids: &[UserId]) -> Option<usize> {
+        let user_ids = user_ids.iter().collect::<HashSet<_>>();
+        self.participants
+            .iter()
+            .position(|participant| participant.iter().collect::<HashSet<_>>() == user_ids)
+    }
+}
+
+/// Pairs up `count` participants in rounds with the circle method, so that
+/// every participant plays every other participant once and at most once per
+/// round.
+fn schedule(count: usize) -> Vec<Vec<[usize; 2]>> {
+    // An odd number of participants gets a placeholder and whoever meets it
+    // sits out the round.
+    let mut circle = (0..count).map(Some).collect::<Vec<_>>();
+    if count % 2 == 1 {
+        circle.push(None);
+    }
+    let size = circle.len();
+
+    let mut rounds = Vec::new();
+    for round in 0..size - 1 {
+        let pairings = (0..size / 2)
+            .filter_map(|index| {
+                let (home, away) = (circle[index]?, circle[size - 1 - index]?);
+                // The fixed participant alternates between home and away.
+                if index == 0 && round % 2 == 1 {
+                    Some([away, home])
+                } else {
+                    Some([home, away])
+                }
+            })
+            .collect();
+        rounds.push(pairings);
+        circle[1..].rotate_right(1);
+    }
+    rounds
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn participants(count: usize) -> Vec<Vec<UserId>> {
+        (0..count)
+            .map(|index| vec![UserId::from(format!("user{}", index))])
+            .collect()
+    }
+
+    fn league(count: usize) -> League {
+        League::new(
+            LeagueId::from("league".to_owned()),
+            "League".to_owned(),
+            participants(count),
+            Scoring::default(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_schedule() {
+        for count in 2..=7 {
+            let rounds = schedule(count);
+            let mut pairs = HashSet::new();
+            for pairings in &rounds {
+                let mut playing = HashSet::new();
+                for [home, away] in pairings {
+                    assert!(playing.insert(*home) && playing.insert(*away));
+                    assert!(pairs.insert((*home.min(away), *home.max(away))));
+                }
+            }
+            assert_eq!(pairs.len(), count * (count - 1) / 2);
+        }
+    }
+
+    #[test]
+    fn test_new_invalid() {
+        let new = |participants| {
+            League::new(
+                LeagueId::from("league".to_owned()),
+                "League".to_owned(),
+                participants,
+                Scoring::default(),
+            )
+            .map(|_| ())
+        };
+        assert_eq!(
+            new(participants(1)),
+            Err(LeagueError::TooFewParticipants {
+                min_participants: MIN_PARTICIPANTS
+            })
+        );
+        assert_eq!(
+            new(vec![participants(1)[0].clone(), vec![]]),
+            Err(LeagueError::EmptyParticipant { participant: 1 })
+        );
+        let a = UserId::from("a".to_owned());
+        assert_eq!(
+            new(vec![
+                vec![a.clone()],
+                vec![a.clone(), UserId::from("b".to_owned())]
+            ]),
+            Err(LeagueError::DuplicatePlayer { user_id: a })
+        );
+    }
+
+    #[test]
+    fn test_record() {
+        let mut league = league(3);
+        let users = participants(3);
+        let game_id = GameId::from("game".to_owned());
+        assert!(league.record(&game_id, &users[2], &users[0]));
+        // The fixture was already played.
+        assert!(!league.record(&game_id, &users[0], &users[2]));
+        // Teams that are no participants have no fixture.
+        assert!(!league.record(
+            &game_id,
+            &[users[0][0].clone(), users[1][0].clone()],
+            &users[2]
+        ));
+
+        assert!(league.record(&game_id, &users[2], &users[1]));
+        assert!(!league.is_finished());
+        assert!(league.record(&game_id, &users[1], &users[0]));
+        assert!(league.is_finished());
+    }
+
+    #[test]
+    fn test_table() {
+        let mut league = league(3);
+        let users = participants(3);
+        let game_id = GameId::from("game".to_owned());
+        league.record(&game_id, &users[2], &users[0]);
+        league.record(&game_id, &users[2], &users[1]);
+        league.record(&game_id, &users[1], &users[0]);
+        assert_eq!(
+            league.table(),
+            vec![
+                TableRow {
+                    participant: 2,
+                    played: 2,
+                    wins: 2,
+                    losses: 0,
+                    points: 6
+                },
+                TableRow {
+                    participant: 1,
+                    played: 2,
+                    wins: 1,
+                    losses: 1,
+                    points: 3
+                },
+                TableRow {
+                    participant: 0,
+                    played: 2,
+                    wins: 0,
+                    losses: 2,
+                    points: 0
+                },
+            ]
+        );
+    }
+}
@@ -1,10 +1,21 @@
 #![feature(async_closure)]
 
 pub mod api;
+pub mod evaluation;
 pub mod skill_base;
+pub mod snapshot;
 pub mod store;
+pub mod webhook;
 
+mod balance;
+mod group_settings;
+mod league;
 mod merge;
 mod message;
 mod player;
+mod rating_system;
+mod smoothing;
+mod statistics;
+mod tournament;
 mod true_skill;
+mod user_name;
@@ -4,6 +4,7 @@ use rocket_db_pools::Database;
 
 use fooskill::api;
 use fooskill::store::Store;
+use fooskill::webhook;
 
 #[get("/<_..>", rank = 100)]
 async fn index() -> Option<NamedFile> {
@@ -15,16 +16,52 @@ fn rocket() -> _ {
     rocket::build()
         .attach(AdHoc::config::<api::GroupKeyConfig>())
         .attach(Store::init())
+        .attach(AdHoc::on_liftoff("Webhook deliveries", |rocket| {
+            Box::pin(async move {
+                if let Some(store) = Store::fetch(rocket) {
+                    rocket::tokio::spawn(webhook::run((**store).clone()));
+                }
+            })
+        }))
         .mount(
             "/api/v1.0/",
             routes![
                 api::get_leaderboard,
+                api::get_smoothed_leaderboard,
+                api::get_ratings,
                 api::get_user,
                 api::get_user_games,
+                api::get_head_to_head,
+                api::get_user_partners,
+                api::get_user_stats,
+                api::get_group_stats,
+                api::get_pairs,
                 api::query_user,
                 api::post_user,
                 api::get_games,
                 api::post_game,
+                api::post_games_batch,
+                api::post_teams_balance,
+                api::get_queue,
+                api::put_queue_user,
+                api::delete_queue_user,
+                api::post_queue_next,
+                api::get_events,
+                api::get_group_settings,
+                api::put_group_settings,
+                api::get_seasons,
+                api::post_season,
+                api::post_tournament,
+                api::get_tournaments,
+                api::get_tournament,
+                api::post_tournament_match,
+                api::post_league,
+                api::get_leagues,
+                api::get_league,
+                api::post_webhook,
+                api::get_webhooks,
+                api::delete_webhook,
+                api::get_webhook_deliveries,
             ],
         )
         .mount("/static", FileServer::from("frontend/static"))
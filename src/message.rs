@@ -32,10 +32,46 @@ impl Message {
     }
 
     /// Removes the belief of the other message from this one.
-    pub fn exclude(&self, rhs: &Message) -> Message {
-        Message {
-            pi: self.pi - rhs.pi,
-            tau: self.tau - rhs.tau,
+    ///
+    /// Returns `None` if rounding leaves no positive precision, so that the
+    /// caller can keep a belief it already has instead of an invalid one.
+    pub fn exclude(&self, rhs: &Message) -> Option<Message> {
+        let pi = self.pi - rhs.pi;
+        if pi > 0.0 && pi.is_finite() {
+            Some(Message {
+                pi,
+                tau: self.tau - rhs.tau,
+            })
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exclude() {
+        let belief = Message::from_mu_sigma2(25.0, 4.0);
+        let update = Message::from_mu_sigma2(30.0, 16.0);
+        let (mu, sigma2) = belief
+            .include(&update)
+            .exclude(&update)
+            .unwrap()
+            .to_mu_sigma2();
+        assert!((mu - 25.0).abs() < 1e-9);
+        assert!((sigma2 - 4.0).abs() < 1e-9);
+
+        // Removing at least as much precision as there is leaves no belief.
+        for rhs in [belief, belief.include(&update)] {
+            assert!(belief.exclude(&rhs).is_none());
         }
+        let infinite = Message {
+            pi: f64::INFINITY,
+            tau: 0.0,
+        };
+        assert!(infinite.exclude(&belief).is_none());
     }
 }
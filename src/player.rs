@@ -13,15 +13,30 @@ pub struct Player {
 
 impl Default for Player {
     fn default() -> Self {
+        Player::new(chrono::Utc::now())
+    }
+}
+
+impl Player {
+    /// Makes a player that has not played yet at the given point in time.
+    pub fn new(datetime: chrono::DateTime<chrono::Utc>) -> Self {
         Player {
             skill: Message::from_mu_sigma2(Player::default_mean(), Player::default_sigma().powi(2)),
-            datetime: chrono::Utc::now(),
+            datetime,
         }
     }
-}
 
-impl Player {
     pub fn skill_at(&self, query: &chrono::DateTime<chrono::Utc>) -> Option<Message> {
+        self.skill_at_with_change_speed(query, Self::default_sigma2_change_speed())
+    }
+
+    /// Returns the skill at a point in time like `skill_at`, but with a custom
+    /// speed at which sigma2 increases per second.
+    pub fn skill_at_with_change_speed(
+        &self,
+        query: &chrono::DateTime<chrono::Utc>,
+        sigma2_change_speed: f64,
+    ) -> Option<Message> {
         let time_delta = *query - self.datetime;
         // The temporal model can only look into the future. Fail here, whenever
         // this gets queried for something clearly in the past.
@@ -31,10 +46,40 @@ impl Player {
         let (mu, sigma2) = self.skill.to_mu_sigma2();
         Some(Message::from_mu_sigma2(
             mu,
-            sigma2 + Self::default_sigma2_change_speed() * (time_delta.num_seconds() as f64),
+            sigma2 + sigma2_change_speed * (time_delta.num_seconds() as f64),
         ))
     }
 
+    /// Returns the latest skill estimate without any change over time.
+    pub fn skill(&self) -> &Message {
+        &self.skill
+    }
+
+    /// Returns the point in time when this player's skill was last updated.
+    pub fn datetime(&self) -> &chrono::DateTime<chrono::Utc> {
+        &self.datetime
+    }
+
+    /// Moves the skill belief toward the one of a new player. Returns `None`
+    /// if the player already has a later skill estimate.
+    ///
+    /// # Arguments
+    ///
+    /// * `datetime` point in time of the move.
+    /// * `amount` how far to move the belief, from 0 for keeping it to 1 for
+    ///   the belief of a new player.
+    pub fn regress(&self, datetime: chrono::DateTime<chrono::Utc>, amount: f64) -> Option<Player> {
+        let (mu, sigma2) = self.skill_at(&datetime)?.to_mu_sigma2();
+        let default_sigma2 = Player::default_sigma().powi(2);
+        Some(Player {
+            skill: Message::from_mu_sigma2(
+                mu + amount * (Player::default_mean() - mu),
+                sigma2 + amount * (default_sigma2 - sigma2),
+            ),
+            datetime,
+        })
+    }
+
     pub fn set_skill(&mut self, skill: Message, datetime: chrono::DateTime<chrono::Utc>) {
         self.skill = skill;
         self.datetime = datetime;
@@ -49,7 +94,55 @@ impl Player {
     }
 
     /// Speed at which sigma2 increases per second.
-    fn default_sigma2_change_speed() -> f64 {
+    pub fn default_sigma2_change_speed() -> f64 {
         20.0 / (chrono::Duration::days(90).num_seconds() as f64)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn player(mu: f64, sigma2: f64, datetime: chrono::DateTime<chrono::Utc>) -> Player {
+        let mut player = Player::new(datetime);
+        player.set_skill(Message::from_mu_sigma2(mu, sigma2), datetime);
+        player
+    }
+
+    #[test]
+    fn test_regress() {
+        let datetime = chrono::Utc::now();
+        let player = player(35.0, 4.0, datetime);
+        let default_sigma2 = Player::default_sigma().powi(2);
+
+        let (mu, sigma2) = player.regress(datetime, 0.0).unwrap().skill.to_mu_sigma2();
+        assert!((mu - 35.0).abs() < 1e-9);
+        assert!((sigma2 - 4.0).abs() < 1e-9);
+
+        let (mu, sigma2) = player.regress(datetime, 1.0).unwrap().skill.to_mu_sigma2();
+        assert!((mu - Player::default_mean()).abs() < 1e-9);
+        assert!((sigma2 - default_sigma2).abs() < 1e-9);
+
+        let (mu, sigma2) = player.regress(datetime, 0.5).unwrap().skill.to_mu_sigma2();
+        assert!((mu - 30.0).abs() < 1e-9);
+        assert!((sigma2 - (4.0 + default_sigma2) / 2.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_regress_later() {
+        let datetime = chrono::Utc::now();
+        let player = player(35.0, 4.0, datetime);
+        let later = datetime + chrono::Duration::days(90);
+
+        // The belief first grows uncertain over time and then moves.
+        let regressed = player.regress(later, 0.0).unwrap();
+        assert_eq!(*regressed.datetime(), later);
+        let (mu, sigma2) = regressed.skill.to_mu_sigma2();
+        assert!((mu - 35.0).abs() < 1e-9);
+        assert!((sigma2 - 24.0).abs() < 1e-6);
+
+        assert!(player
+            .regress(datetime - chrono::Duration::days(1), 0.5)
+            .is_none());
+    }
+}
@@ -0,0 +1,455 @@
+use std::collections::HashMap;
+use std::f64;
+
+use rocket::form::FromFormField;
+use serde::{Deserialize, Serialize};
+
+use crate::skill_base::UserId;
+use crate::true_skill::TrueSkill;
+
+/// Rates players from the outcomes of games between two teams.
+pub trait RatingSystem {
+    /// What the system believes about the skill of a player.
+    type Rating: Clone + Serialize;
+
+    /// Rating of a player that has not played yet.
+    fn initial(&self) -> Self::Rating;
+
+    /// Rates a game and returns the new ratings of the winners and the
+    /// losers in the same order.
+    fn rate(
+        &self,
+        winners: &[Self::Rating],
+        losers: &[Self::Rating],
+    ) -> (Vec<Self::Rating>, Vec<Self::Rating>);
+
+    /// Rates a game like `rate`, where every rating comes with the fraction
+    /// of the game the player played. Systems that cannot weight players
+    /// count everyone as having played the whole game.
+    fn rate_weighted(
+        &self,
+        winners: &[(f64, Self::Rating)],
+        losers: &[(f64, Self::Rating)],
+    ) -> (Vec<Self::Rating>, Vec<Self::Rating>) {
+        let ratings = |team: &[(f64, Self::Rating)]| {
+            team.iter()
+                .map(|(_, rating)| rating.clone())
+                .collect::<Vec<_>>()
+        };
+        self.rate(&ratings(winners), &ratings(losers))
+    }
+
+    /// Number that orders players on a leaderboard, higher is better.
+    fn score(&self, rating: &Self::Rating) -> f64;
+}
+
+/// Rating systems a group can compare.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash, FromFormField)]
+#[serde(rename_all = "snake_case")]
+pub enum RatingSystemKind {
+    #[field(value = "true_skill")]
+    TrueSkill,
+    Elo,
+    Glicko2,
+}
+
+/// Rating of a player in some rating system, ready to show.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Summary {
+    pub score: f64,
+    pub rating: serde_json::Value,
+}
+
+impl RatingSystemKind {
+    /// Name of the rating system as it appears in requests.
+    pub fn name(self) -> &'static str {
+        match self {
+            RatingSystemKind::TrueSkill => "true_skill",
+            RatingSystemKind::Elo => "elo",
+            RatingSystemKind::Glicko2 => "glicko2",
+        }
+    }
+
+    /// Rates all games in order and summarizes the final rating of every
+    /// player.
+    ///
+    /// # Arguments
+    ///
+    /// * `true_skill` TrueSkill estimator to use for `TrueSkill`.
+    /// * `games` user IDs of the winners and the losers of all games in
+    ///   chronological order.
+    pub fn replay(
+        self,
+        true_skill: &TrueSkill,
+        games: &[(Vec<UserId>, Vec<UserId>)],
+    ) -> HashMap<UserId, Summary> {
+        match self {
+            RatingSystemKind::TrueSkill => summarize(true_skill, games),
+            RatingSystemKind::Elo => summarize(&Elo::default(), games),
+            RatingSystemKind::Glicko2 => summarize(&Glicko2::default(), games),
+        }
+    }
+}
+
+fn summarize<S: RatingSystem>(
+    system: &S,
+    games: &[(Vec<UserId>, Vec<UserId>)],
+) -> HashMap<UserId, Summary> {
+    replay(system, games)
+        .into_iter()
+        .map(|(user_id, rating)| {
+            let summary = Summary {
+                score: system.score(&rating),
+                rating: serde_json::to_value(&rating).unwrap_or(serde_json::Value::Null),
+            };
+            (user_id, summary)
+        })
+        .collect()
+}
+
+/// Rates all games in order and returns the final rating of every player.
+///
+/// # Arguments
+///
+/// * `system` rating system to use.
+/// * `games` user IDs of the winners and the losers of all games in
+///   chronological order.
+pub fn replay<S: RatingSystem>(
+    system: &S,
+    games: &[(Vec<UserId>, Vec<UserId>)],
+) -> HashMap<UserId, S::Rating> {
+    let mut ratings: HashMap<UserId, S::Rating> = HashMap::new();
+    for (winner_ids, loser_ids) in games {
+        let mut team_ratings = |user_ids: &[UserId]| {
+            user_ids
+                .iter()
+                .map(|user_id| {
+                    ratings
+                        .entry(user_id.clone())
+                        .or_insert_with(|| system.initial())
+                        .clone()
+                })
+                .collect::<Vec<_>>()
+        };
+        let winners = team_ratings(winner_ids);
+        let losers = team_ratings(loser_ids);
+        let (winners, losers) = system.rate(&winners, &losers);
+        for (user_id, rating) in winner_ids
+            .iter()
+            .zip(winners)
+            .chain(loser_ids.iter().zip(losers))
+        {
+            ratings.insert(user_id.clone(), rating);
+        }
+    }
+    ratings
+}
+
+/// Elo rating system. Teams play with the mean rating of their players.
+pub struct Elo {
+    /// Rating of new players.
+    initial: f64,
+    /// Maximal change of a rating in a single game.
+    k: f64,
+}
+
+impl Default for Elo {
+    fn default() -> Self {
+        Elo {
+            initial: 1500.0,
+            k: 32.0,
+        }
+    }
+}
+
+impl Elo {
+    /// Returns the probability that a player with rating `rating` beats a
+    /// player with rating `opponent`.
+    fn expected(rating: f64, opponent: f64) -> f64 {
+        1.0 / (1.0 + 10_f64.powf((opponent - rating) / 400.0))
+    }
+}
+
+impl RatingSystem for Elo {
+    type Rating = f64;
+
+    fn initial(&self) -> f64 {
+        self.initial
+    }
+
+    fn rate(&self, winners: &[f64], losers: &[f64]) -> (Vec<f64>, Vec<f64>) {
+        let mean = |team: &[f64]| team.iter().sum::<f64>() / team.len() as f64;
+        let change = self.k * (1.0 - Elo::expected(mean(winners), mean(losers)));
+        (
+            winners.iter().map(|rating| rating + change).collect(),
+            losers.iter().map(|rating| rating - change).collect(),
+        )
+    }
+
+    fn score(&self, rating: &f64) -> f64 {
+        *rating
+    }
+}
+
+/// Glicko-2 rating of a player.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct Glicko2Rating {
+    pub rating: f64,
+    /// Rating deviation.
+    pub deviation: f64,
+    pub volatility: f64,
+}
+
+/// Glicko-2 rating system. Every game is a rating period of its own, in
+/// which each player plays against a composite of the opposing team.
+pub struct Glicko2 {
+    /// Constrains the change of the volatility over time.
+    tau: f64,
+}
+
+impl Default for Glicko2 {
+    fn default() -> Self {
+        Glicko2 { tau: 0.5 }
+    }
+}
+
+/// Converts between the Glicko and the Glicko-2 scale.
+const GLICKO2_SCALE: f64 = 173.7178;
+
+/// Smallest expected score an update counts with, so that a huge rating gap
+/// does not make the estimated variance infinite.
+const MIN_EXPECTED: f64 = 1e-10;
+
+impl Glicko2 {
+    fn g(phi: f64) -> f64 {
+        1.0 / (1.0 + 3.0 * phi.powi(2) / f64::consts::PI.powi(2)).sqrt()
+    }
+
+    fn expected(mu: f64, opponent_mu: f64, opponent_phi: f64) -> f64 {
+        1.0 / (1.0 + (-Glicko2::g(opponent_phi) * (mu - opponent_mu)).exp())
+    }
+
+    /// Updates a rating after a rating period.
+    ///
+    /// # Arguments
+    ///
+    /// * `rating` rating before the period.
+    /// * `results` ratings of all opponents together with the score against
+    ///   them, 1 for a win and 0 for a loss.
+    fn update(&self, rating: &Glicko2Rating, results: &[(Glicko2Rating, f64)]) -> Glicko2Rating {
+        let mu = (rating.rating - 1500.0) / GLICKO2_SCALE;
+        let phi = rating.deviation / GLICKO2_SCALE;
+        let opponents = results
+            .iter()
+            .map(|(opponent, score)| {
+                let opponent_mu = (opponent.rating - 1500.0) / GLICKO2_SCALE;
+                let opponent_phi = opponent.deviation / GLICKO2_SCALE;
+                (
+                    Glicko2::g(opponent_phi),
+                    Glicko2::expected(mu, opponent_mu, opponent_phi)
+                        .clamp(MIN_EXPECTED, 1.0 - MIN_EXPECTED),
+                    *score,
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let v = 1.0
+            / opponents
+                .iter()
+                .map(|(g, expected, _)| g.powi(2) * expected * (1.0 - expected))
+                .sum::<f64>();
+        let improvement = opponents
+            .iter()
+            .map(|(g, expected, score)| g * (score - expected))
+            .sum::<f64>();
+        let delta = v * improvement;
+
+        // Finds the new volatility with the Illinois algorithm.
+        let a = rating.volatility.powi(2).ln();
+        let f = |x: f64| {
+            let ex = x.exp();
+            ex * (delta.powi(2) - phi.powi(2) - v - ex) / (2.0 * (phi.powi(2) + v + ex).powi(2))
+                - (x - a) / self.tau.powi(2)
+        };
+        let mut lower = a;
+        let mut upper = if delta.powi(2) > phi.powi(2) + v {
+            (delta.powi(2) - phi.powi(2) - v).ln()
+        } else {
+            let mut k = 1.0;
+            while f(a - k * self.tau) < 0.0 {
+                k += 1.0;
+            }
+            a - k * self.tau
+        };
+        let (mut f_lower, mut f_upper) = (f(lower), f(upper));
+        while (upper - lower).abs() > 1e-6 {
+            let c = lower + (lower - upper) * f_lower / (f_upper - f_lower);
+            let f_c = f(c);
+            if f_c * f_upper <= 0.0 {
+                lower = upper;
+                f_lower = f_upper;
+            } else {
+                f_lower /= 2.0;
+            }
+            upper = c;
+            f_upper = f_c;
+        }
+        let volatility = (lower / 2.0).exp();
+
+        let phi_star = (phi.powi(2) + volatility.powi(2)).sqrt();
+        let new_phi = 1.0 / (1.0 / phi_star.powi(2) + 1.0 / v).sqrt();
+        let new_mu = mu + new_phi.powi(2) * improvement;
+        Glicko2Rating {
+            rating: 1500.0 + GLICKO2_SCALE * new_mu,
+            deviation: GLICKO2_SCALE * new_phi,
+            volatility,
+        }
+    }
+
+    /// Combines the players of a team into a single opponent with their mean
+    /// rating and deviation.
+    fn composite(team: &[Glicko2Rating]) -> Glicko2Rating {
+        let count = team.len() as f64;
+        Glicko2Rating {
+            rating: team.iter().map(|player| player.rating).sum::<f64>() / count,
+            deviation: (team
+                .iter()
+                .map(|player| player.deviation.powi(2))
+                .sum::<f64>()
+                / count)
+                .sqrt(),
+            volatility: team.iter().map(|player| player.volatility).sum::<f64>() / count,
+        }
+    }
+}
+
+impl RatingSystem for Glicko2 {
+    type Rating = Glicko2Rating;
+
+    fn initial(&self) -> Glicko2Rating {
+        Glicko2Rating {
+            rating: 1500.0,
+            deviation: 350.0,
+            volatility: 0.06,
+        }
+    }
+
+    fn rate(
+        &self,
+        winners: &[Glicko2Rating],
+        losers: &[Glicko2Rating],
+    ) -> (Vec<Glicko2Rating>, Vec<Glicko2Rating>) {
+        let (winner_composite, loser_composite) =
+            (Glicko2::composite(winners), Glicko2::composite(losers));
+        (
+            winners
+                .iter()
+                .map(|rating| self.update(rating, &[(loser_composite, 1.0)]))
+                .collect(),
+            losers
+                .iter()
+                .map(|rating| self.update(rating, &[(winner_composite, 0.0)]))
+                .collect(),
+        )
+    }
+
+    /// Conservative estimate two deviations below the rating.
+    fn score(&self, rating: &Glicko2Rating) -> f64 {
+        rating.rating - 2.0 * rating.deviation
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_elo() {
+        let elo = Elo::default();
+        let (winners, losers) = elo.rate(&[1500.0], &[1500.0]);
+        assert_eq!(winners, vec![1516.0]);
+        assert_eq!(losers, vec![1484.0]);
+
+        // Beating a much weaker team barely changes the ratings, and all
+        // players of a team change by the same amount.
+        let (winners, losers) = elo.rate(&[1900.0, 1700.0], &[1300.0, 1300.0]);
+        let change = winners[0] - 1900.0;
+        assert!(change > 0.0 && change < 2.0);
+        assert!((winners[1] - 1700.0 - change).abs() < 1e-9);
+        assert!((1300.0 - losers[0] - change).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_elo_rate_weighted() {
+        // Elo has no weights and counts everyone fully.
+        let elo = Elo::default();
+        assert_eq!(
+            elo.rate_weighted(&[(0.5, 1500.0)], &[(1.0, 1500.0)]),
+            elo.rate(&[1500.0], &[1500.0])
+        );
+    }
+
+    #[test]
+    fn test_glicko2_update() {
+        // Example of the Glicko-2 paper by Mark Glickman.
+        let glicko2 = Glicko2 { tau: 0.5 };
+        let opponent = |rating, deviation| Glicko2Rating {
+            rating,
+            deviation,
+            volatility: 0.06,
+        };
+        let updated = glicko2.update(
+            &opponent(1500.0, 200.0),
+            &[
+                (opponent(1400.0, 30.0), 1.0),
+                (opponent(1550.0, 100.0), 0.0),
+                (opponent(1700.0, 300.0), 0.0),
+            ],
+        );
+        assert!((updated.rating - 1464.05).abs() < 0.01);
+        assert!((updated.deviation - 151.52).abs() < 0.01);
+        assert!((updated.volatility - 0.05999).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_glicko2_update_huge_gap() {
+        let glicko2 = Glicko2::default();
+        let rating = |rating| Glicko2Rating {
+            rating,
+            deviation: 50.0,
+            volatility: 0.06,
+        };
+
+        let expected = glicko2.update(&rating(1500.0), &[(rating(1e6), 0.0)]);
+        assert!(expected.rating.is_finite() && expected.rating <= 1500.0);
+        assert!(expected.deviation.is_finite() && expected.deviation > 0.0);
+        assert!(expected.volatility.is_finite());
+
+        // Beating a far stronger opponent is a surprise that raises the
+        // volatility.
+        let upset = glicko2.update(&rating(1500.0), &[(rating(1e6), 1.0)]);
+        assert!(upset.rating.is_finite() && upset.rating > 1500.0);
+        assert!(upset.deviation.is_finite() && upset.deviation > 0.0);
+        assert!(upset.volatility.is_finite() && upset.volatility > 0.06);
+    }
+
+    #[test]
+    fn test_replay() {
+        let id = |name: &str| UserId::from(name.to_owned());
+        let games = vec![
+            (vec![id("a")], vec![id("b")]),
+            (vec![id("a")], vec![id("c")]),
+            (vec![id("b")], vec![id("c")]),
+        ];
+        for kind in [
+            RatingSystemKind::TrueSkill,
+            RatingSystemKind::Elo,
+            RatingSystemKind::Glicko2,
+        ] {
+            let summaries = kind.replay(&TrueSkill::new(25.0 / 6.0, 0.0), &games);
+            assert_eq!(summaries.len(), 3);
+            assert!(summaries[&id("a")].score > summaries[&id("b")].score);
+            assert!(summaries[&id("b")].score > summaries[&id("c")].score);
+        }
+    }
+}
@@ -1,27 +1,74 @@
 use std::cmp::PartialOrd;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::convert::TryInto;
 use std::ops::{Deref, DerefMut};
 
 use async_trait::async_trait;
+use chrono::TimeZone;
 use derive_more::From;
 use quick_error::quick_error;
-use rocket::form::FromForm;
+use rocket::form::{FromForm, FromFormField};
+use rocket::futures::{Stream, StreamExt};
 use rocket::serde::{Deserialize, Serialize};
 use rocket_db_pools::deadpool_redis::{
     redis::{self, AsyncCommands},
     Connection,
 };
+use sha2::{Digest, Sha256};
 
+use crate::balance::{self, Lineup};
+use crate::group_settings::{GameError, GroupSettings, SettingsError, Team};
+use crate::league::{League, LeagueError, Scoring};
 use crate::merge;
+use crate::message::Message;
 use crate::player::Player;
-use crate::true_skill::{GameResult, TrueSkill};
-
-#[derive(Clone)]
+use crate::rating_system::{RatingSystem, RatingSystemKind, Summary};
+use crate::smoothing;
+use crate::statistics::{self, HeadToHead, OpponentStatistics, PairStatistics, UserStatistics};
+use crate::tournament::{Format, Tournament, TournamentError};
+use crate::true_skill::TrueSkill;
+use crate::user_name;
+use crate::webhook::{Delivery, DeliveryStatus, Webhook};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct GroupId(String);
-#[derive(Clone, From, Debug, Serialize, Deserialize, FromForm)]
+#[derive(Clone, From, Debug, PartialEq, Eq, Serialize, Deserialize, FromForm)]
 pub struct GameId(String);
-#[derive(Clone, Debug, PartialEq, Eq, From, Serialize, Deserialize, Hash)]
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, From, Serialize, Deserialize, Hash)]
 pub struct UserId(String);
+#[derive(Clone, Debug, PartialEq, Eq, From, Serialize, Deserialize)]
+pub struct WebhookId(String);
+#[derive(Clone, Debug, PartialEq, Eq, From, Serialize, Deserialize)]
+pub struct TournamentId(String);
+#[derive(Clone, Debug, PartialEq, Eq, From, Serialize, Deserialize)]
+pub struct LeagueId(String);
+
+/// Role of a player in a team of two.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize, FromFormField)]
+#[serde(rename_all = "snake_case")]
+pub enum Position {
+    Offense,
+    Defense,
+}
+
+/// Side of a table a team plays on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Side {
+    Home,
+    Away,
+}
+
+/// Selects one of the skills of a user.
+#[derive(Clone, Copy, Debug)]
+pub enum SkillKind<'a> {
+    /// Skill across all games.
+    Overall,
+    /// Skill in the games of a game mode.
+    Mode(&'a str),
+    /// Skill when playing in a position.
+    Position(Position),
+}
 
 impl redis::FromRedisValue for GameId {
     fn from_redis_value(v: &redis::Value) -> redis::RedisResult<GameId> {
@@ -113,9 +160,44 @@ quick_error! {
             cause(err)
                 from()
         }
+        InvalidUserName(err: user_name::NameError) {
+            display("invalid user name: {}", err)
+                from()
+        }
         UserAlreadyExists {}
-        UserNameTooShort {}
+        EmptyQuery {}
+        IdempotencyKeyReused {}
+        InvalidGame(err: GameError) {
+            display("invalid game: {}", err)
+                from()
+        }
+        InvalidBatchGame(index: usize, err: GameError) {
+            display("invalid game at position {}: {}", index, err)
+        }
+        InvalidGroupSettings(err: SettingsError) {
+            display("invalid group settings: {}", err)
+                from()
+        }
         InvalidGroupId {}
+        UnknownGameMode {}
+        AmbiguousSkillKind {}
+        SeasonLeaderboardNotArchived {}
+        InvalidPlayerCount {}
+        NotEnoughQueued {}
+        InvalidSeasonReset {}
+        SeasonNotFound {}
+        InvalidTournament(err: TournamentError) {
+            display("invalid tournament: {}", err)
+                from()
+        }
+        TournamentNotFound {}
+        InvalidLeague(err: LeagueError) {
+            display("invalid league: {}", err)
+                from()
+        }
+        LeagueNotFound {}
+        InvalidWebhookUrl {}
+        WebhookNotFound {}
     }
 }
 
@@ -124,6 +206,12 @@ pub struct User {
     id: UserId,
     name: String,
     player: Player,
+    /// Players of all game modes the user played in.
+    #[serde(default)]
+    mode_players: HashMap<String, Player>,
+    /// Players of all positions the user played in.
+    #[serde(default)]
+    position_players: HashMap<Position, Player>,
 }
 
 impl User {
@@ -138,6 +226,16 @@ impl User {
     pub fn player(&self) -> &Player {
         &self.player
     }
+
+    /// Returns the player that holds a kind of skill. Returns `None` if the
+    /// user never played in the game mode or position.
+    pub fn player_in(&self, kind: SkillKind) -> Option<&Player> {
+        match kind {
+            SkillKind::Overall => Some(&self.player),
+            SkillKind::Mode(mode) => self.mode_players.get(mode),
+            SkillKind::Position(position) => self.position_players.get(&position),
+        }
+    }
 }
 
 #[derive(Serialize, Clone, Deserialize, Debug)]
@@ -146,9 +244,69 @@ pub struct Game {
     datetime: chrono::DateTime<chrono::Utc>,
     winner_ids: Vec<UserId>,
     loser_ids: Vec<UserId>,
+    /// Game mode with separate ratings the game belongs to.
+    #[serde(default)]
+    mode: Option<String>,
+    /// Positions of the players. Either empty or set for every player.
+    #[serde(default)]
+    positions: HashMap<UserId, Position>,
+    /// Fractions of the game the players played. Players without a fraction
+    /// played the whole game.
+    #[serde(default)]
+    weights: HashMap<UserId, f64>,
+    /// Table the game was played on.
+    #[serde(default)]
+    table: Option<String>,
+    /// Side of the table the winners played on.
+    #[serde(default)]
+    winner_side: Option<Side>,
+    /// How the skills of the players changed through this game.
+    #[serde(default)]
+    skill_changes: Vec<SkillChange>,
 }
 
 impl Game {
+    pub fn new(
+        id: GameId,
+        datetime: chrono::DateTime<chrono::Utc>,
+        winner_ids: Vec<UserId>,
+        loser_ids: Vec<UserId>,
+    ) -> Self {
+        Game {
+            id,
+            datetime,
+            winner_ids,
+            loser_ids,
+            mode: None,
+            positions: HashMap::new(),
+            weights: HashMap::new(),
+            table: None,
+            winner_side: None,
+            skill_changes: Vec::new(),
+        }
+    }
+
+    /// Sets the positions the players played in.
+    pub fn with_positions(mut self, positions: HashMap<UserId, Position>) -> Self {
+        self.positions = positions;
+        self
+    }
+
+    /// Sets the fractions of the game players played, e.g. because they
+    /// were substituted.
+    pub fn with_weights(mut self, weights: HashMap<UserId, f64>) -> Self {
+        self.weights = weights;
+        self
+    }
+
+    /// Sets the table the game was played on and the side of the winners.
+    /// Games without a table share the advantage of their sides.
+    pub fn with_side(mut self, table: Option<String>, winner_side: Side) -> Self {
+        self.table = table;
+        self.winner_side = Some(winner_side);
+        self
+    }
+
     pub fn winner_ids(&self) -> &Vec<UserId> {
         &self.winner_ids
     }
@@ -164,6 +322,116 @@ impl Game {
     pub fn datetime(&self) -> &chrono::DateTime<chrono::Utc> {
         &self.datetime
     }
+
+    pub fn mode(&self) -> Option<&str> {
+        self.mode.as_deref()
+    }
+
+    pub fn positions(&self) -> &HashMap<UserId, Position> {
+        &self.positions
+    }
+
+    pub fn weights(&self) -> &HashMap<UserId, f64> {
+        &self.weights
+    }
+
+    pub fn table(&self) -> Option<&str> {
+        self.table.as_deref()
+    }
+
+    pub fn winner_side(&self) -> Option<Side> {
+        self.winner_side
+    }
+
+    /// Fraction of the game the user played.
+    pub fn weight(&self, user_id: &UserId) -> f64 {
+        self.weights.get(user_id).copied().unwrap_or(1.0)
+    }
+
+    pub fn skill_changes(&self) -> &Vec<SkillChange> {
+        &self.skill_changes
+    }
+
+    /// Checks that either no or all players have a position. Only teams of two
+    /// have positions, one offense and one defense player.
+    fn validate_positions(&self) -> Result<(), GameError> {
+        if self.positions.is_empty() {
+            return Ok(());
+        }
+        let user_ids = self
+            .winner_ids
+            .iter()
+            .chain(&self.loser_ids)
+            .collect::<HashSet<_>>();
+        if let Some(user_id) = self
+            .positions
+            .keys()
+            .find(|user_id| !user_ids.contains(user_id))
+        {
+            return Err(GameError::PositionOfNonPlayer {
+                user_id: user_id.clone(),
+            });
+        }
+        if let Some(user_id) = self
+            .winner_ids
+            .iter()
+            .chain(&self.loser_ids)
+            .find(|user_id| !self.positions.contains_key(user_id))
+        {
+            return Err(GameError::MissingPosition {
+                user_id: user_id.clone(),
+            });
+        }
+        for (team, ids) in [
+            (Team::Winners, &self.winner_ids),
+            (Team::Losers, &self.loser_ids),
+        ] {
+            if ids.len() != 2 {
+                return Err(GameError::PositionsNotAllowed {
+                    team,
+                    size: ids.len(),
+                });
+            }
+            let positions = ids
+                .iter()
+                .map(|user_id| self.positions[user_id])
+                .collect::<HashSet<_>>();
+            if !positions.contains(&Position::Offense) || !positions.contains(&Position::Defense) {
+                return Err(GameError::InvalidPositions { team });
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that only players have a fraction of the game and that it lies
+    /// in (0, 1].
+    fn validate_weights(&self) -> Result<(), GameError> {
+        let user_ids = self
+            .winner_ids
+            .iter()
+            .chain(&self.loser_ids)
+            .collect::<HashSet<_>>();
+        if let Some(user_id) = self
+            .weights
+            .keys()
+            .find(|user_id| !user_ids.contains(user_id))
+        {
+            return Err(GameError::WeightOfNonPlayer {
+                user_id: user_id.clone(),
+            });
+        }
+        if let Some(user_id) = self
+            .winner_ids
+            .iter()
+            .chain(&self.loser_ids)
+            .find(|user_id| !(self.weight(user_id) > 0.0 && self.weight(user_id) <= 1.0))
+        {
+            return Err(GameError::InvalidWeight {
+                user_id: user_id.clone(),
+            });
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug)]
@@ -320,29 +588,82 @@ macro_rules! commit {
     }};
 }
 
-async fn query_user_index(
+/// Returns the lexicographical range that covers all index entries starting
+/// with `prefix`.
+fn prefix_range(prefix: &str) -> (Vec<u8>, Vec<u8>) {
+    let min = [b"[", prefix.as_bytes()].concat();
+    // 0xff never shows up in UTF-8 encoded strings, so it is larger than any
+    // continuation of the prefix.
+    let max = [b"[", prefix.as_bytes(), &[0xff_u8]].concat();
+    (min, max)
+}
+
+/// Number of index entries read at once while scanning a range of the user
+/// name index.
+const USER_INDEX_PAGE_SIZE: isize = 1000;
+
+/// Score of a user in the activity index, which is when they last played in
+/// milliseconds since the Unix epoch.
+fn activity_score(datetime: &chrono::DateTime<chrono::Utc>) -> f64 {
+    datetime.timestamp_millis() as f64
+}
+
+/// Splits an entry of the user name token index, which is of the form
+/// `token:normalized name:user ID`, into the user ID and the name.
+fn parse_token_entry(entry: &str) -> Option<(UserId, String)> {
+    let (name, user_id) = entry.split_once(':')?.1.rsplit_once(':')?;
+    Some((UserId(user_id.to_string()), name.to_string()))
+}
+
+/// Finds all users with a name word that starts with `prefix` together with
+/// their normalized names.
+async fn read_user_index_range(
     con: &mut Connection,
     group_id: &GroupId,
-    query: &str,
-) -> Result<Vec<UserId>, Error> {
-    let entries: Vec<String> = con
-        .zrangebylex_limit(
-            user_name_index_key(group_id),
-            "[".to_owned() + query,
-            "[".to_owned() + query + std::str::from_utf8(&[0x7f_u8]).unwrap(),
-            0,
-            10,
-        )
-        .await?;
-
-    let mut user_ids = Vec::new();
-    for entry in entries {
-        let splits = entry.split(':').collect::<Vec<_>>();
-        if splits.len() >= 2 {
-            user_ids.push(UserId((*splits.last().unwrap()).to_string()));
+    prefix: &str,
+) -> Result<HashMap<UserId, String>, Error> {
+    let (min, max) = prefix_range(prefix);
+    let mut user_names = HashMap::new();
+    let mut offset = 0;
+    loop {
+        let entries: Vec<String> = con
+            .zrangebylex_limit(
+                user_name_token_index_key(group_id),
+                &min,
+                &max,
+                offset,
+                USER_INDEX_PAGE_SIZE,
+            )
+            .await?;
+        user_names.extend(entries.iter().filter_map(|entry| parse_token_entry(entry)));
+        if (entries.len() as isize) < USER_INDEX_PAGE_SIZE {
+            return Ok(user_names);
         }
+        offset += USER_INDEX_PAGE_SIZE;
+    }
+}
+
+/// Finds all users with name words that start with all the words of the query
+/// together with their normalized names.
+async fn query_user_index(
+    con: &mut Connection,
+    group_id: &GroupId,
+    query_tokens: &[String],
+) -> Result<HashMap<UserId, String>, Error> {
+    let mut candidates: Option<HashMap<UserId, String>> = None;
+    for query_token in query_tokens {
+        // All matches of every word are needed, otherwise the intersection
+        // misses users.
+        let user_names = read_user_index_range(con, group_id, query_token).await?;
+        candidates = Some(match candidates {
+            Some(mut candidates) => {
+                candidates.retain(|user_id, _| user_names.contains_key(user_id));
+                candidates
+            }
+            None => user_names,
+        });
     }
-    Ok(user_ids)
+    Ok(candidates.unwrap_or_default())
 }
 
 /// Reads all users given by a vector of user IDs.
@@ -369,7 +690,8 @@ pub async fn read_users(
 
 /// Creates a user with the given name.
 ///
-/// If a user with the same ID already exists, it will be overwritten.
+/// If a user with the same ID already exists, it will be overwritten. The name
+/// gets trimmed and has to be valid and unique when ignoring case and accents.
 ///
 /// # Arguments
 ///
@@ -382,24 +704,22 @@ pub async fn create_user(
     user_id: &UserId,
     name: &str,
 ) -> Result<User, Error> {
-    if name.len() < 3 {
-        return Err(Error::UserNameTooShort);
-    }
+    let name = user_name::validate(name)?;
     let key = user_key(group_id, user_id);
-    let index_entry = name.to_owned() + ":" + &user_id.0;
+    let normalized_name = user_name::normalize(&name);
+    let index_entry = normalized_name.clone() + ":" + &user_id.0;
 
     let user_name_index = user_name_index_key(group_id);
-    commit!(&mut *con, pipe, {
+    let user = commit!(&mut *con, pipe, {
         // Verify that the user does yet exist.
-        redis::cmd("WATCH").arg(&key).query_async(con).await?;
+        redis::cmd("WATCH")
+            .arg(&key)
+            .arg(&user_name_index)
+            .query_async(con)
+            .await?;
+        let (min, max) = prefix_range(&(normalized_name.clone() + ":"));
         let entries: Vec<String> = con
-            .zrangebylex_limit(
-                &user_name_index,
-                "[".to_owned() + name + ":",
-                "[".to_owned() + name + ":" + std::str::from_utf8(&[0x7f_u8]).unwrap(),
-                0,
-                1,
-            )
+            .zrangebylex_limit(&user_name_index, min, max, 0, 1)
             .await?;
         if !entries.is_empty() {
             return Err(Error::UserAlreadyExists);
@@ -407,8 +727,10 @@ pub async fn create_user(
 
         let user = User {
             id: user_id.to_owned(),
-            name: name.to_owned(),
+            name: name.clone(),
             player: Default::default(),
+            mode_players: HashMap::new(),
+            position_players: HashMap::new(),
         };
         // TODO(mkiefel): Move this into the merge logic.
         let node: merge::Mergeable<UserId, User> =
@@ -418,9 +740,49 @@ pub async fn create_user(
             .zadd(&user_name_index, index_entry.clone(), 0_f32)
             .ignore()
             .sadd(user_id_key(group_id), &user_id.0)
+            .ignore()
+            .zadd(
+                leaderboard_index_key(group_id),
+                &user_id.0,
+                index_score(&user.player),
+            )
+            .ignore();
+        pipe.zadd(
+            user_activity_key(group_id),
+            &user_id.0,
+            activity_score(user.player.datetime()),
+        )
+        .ignore();
+        for token in user_name::tokenize(&name) {
+            pipe.zadd(
+                user_name_token_index_key(group_id),
+                token + ":" + &index_entry,
+                0_f32,
+            )
             .ignore();
+        }
         Ok(user)
-    })
+    })?;
+    publish_event(
+        con,
+        group_id,
+        &GroupEvent::UserCreated { user: user.clone() },
+    )
+    .await;
+    Ok(user)
+}
+
+/// Reads all games of a user, most recent first.
+async fn read_user_games(
+    con: &mut Connection,
+    group_id: &GroupId,
+    user_id: &UserId,
+) -> Result<Vec<Game>, Error> {
+    let game_ids: Vec<GameId> = con
+        .zrevrange(user_games_key(group_id, user_id), 0, -1)
+        .await?;
+    // Games never will be deleted, so there is no race here.
+    read_games(con, group_id, &game_ids).await
 }
 
 /// Reads the last 100 games from a user.
@@ -438,207 +800,2300 @@ pub async fn get_recent_games(
 }
 
 /// Finds users whose name match the query.
+///
+/// Matching ignores case and accents and every word of the query may match the
+/// beginning of any word of the name. Users are ordered by how well their name
+/// matches and then by how recently they played. Queries need at least one
+/// word.
 pub async fn query_user(
     con: &mut Connection,
     group_id: &GroupId,
     query: &str,
 ) -> Result<Vec<User>, Error> {
-    // TODO(mkiefel): Implement some form of pagination for this.
-    let user_ids = query_user_index(con, group_id, query).await?;
+    let query_tokens = user_name::tokenize(query);
+    if query_tokens.is_empty() {
+        return Err(Error::EmptyQuery);
+    }
+    // Users are ranked from the indices, only the returned ones are read.
+    let mut ranked_user_ids = query_user_index(con, group_id, &query_tokens)
+        .await?
+        .into_iter()
+        .filter_map(|(user_id, name)| {
+            user_name::relevance(&name, query).map(|score| (score, user_id))
+        })
+        .collect::<Vec<_>>();
+    if ranked_user_ids.is_empty() {
+        return Ok(Vec::new());
+    }
+    let mut pipe = redis::pipe();
+    for (_, user_id) in &ranked_user_ids {
+        pipe.zscore(user_activity_key(group_id), &user_id.0);
+    }
+    let activities: Vec<Option<f64>> = pipe.query_async(con).await?;
+    let activities = ranked_user_ids
+        .iter()
+        .map(|(_, user_id)| user_id.clone())
+        .zip(
+            activities
+                .into_iter()
+                .map(|activity| activity.unwrap_or(0.0)),
+        )
+        .collect::<HashMap<_, _>>();
+    ranked_user_ids.sort_by(|(score_a, user_id_a), (score_b, user_id_b)| {
+        score_b
+            .cmp(score_a)
+            .then_with(|| by_descending_score(activities[user_id_a], activities[user_id_b]))
+    });
+
+    let user_ids = ranked_user_ids
+        .into_iter()
+        .take(10)
+        .map(|(_, user_id)| user_id)
+        .collect::<Vec<_>>();
     // Users never will be deleted, so there is no race here.
     read_users(con, group_id, &user_ids).await
 }
 
 /// Reads the top 100 users.
+///
+/// Only users that have the kind of skill are ranked, e.g. the ones that
+/// played in a game mode. The overall leaderboard reads the leaderboard index,
+/// so it has the same ranks that games report in their rating changes.
 pub async fn get_leaderboard(
     con: &mut Connection,
     group_id: &GroupId,
     datetime: &chrono::DateTime<chrono::Utc>,
+    kind: SkillKind<'_>,
 ) -> Result<Vec<User>, Error> {
+    if let SkillKind::Mode(mode) = kind {
+        if !read_group_settings(con, group_id)
+            .await?
+            .has_game_mode(mode)
+        {
+            return Err(Error::UnknownGameMode);
+        }
+    }
+
+    if let SkillKind::Overall = kind {
+        let user_ids: Vec<UserId> = con
+            .zrevrange(leaderboard_index_key(group_id), 0, 99)
+            .await?;
+        return read_users(con, group_id, &user_ids).await;
+    }
+
     // TODO(mkiefel): Implement some form of pagination for this.
     let user_ids: Vec<UserId> = con.smembers(user_id_key(group_id)).await?;
     // Users never will be deleted, so there is no race here.
     let mut users = read_users(con, &group_id, &user_ids).await?;
+    users.retain(|user| user.player_in(kind).is_some());
     users.sort_unstable_by(|user_a, user_b| {
-        let score_a = -map_score(user_a, datetime);
-        let score_b = -map_score(user_b, datetime);
-        score_a.partial_cmp(&score_b).unwrap()
+        by_descending_score(
+            map_score(user_a.player_in(kind).unwrap(), datetime),
+            map_score(user_b.player_in(kind).unwrap(), datetime),
+        )
     });
     Ok(users)
 }
 
-/// Reads all games given by the vector of game IDs.
-pub async fn read_games(
+/// Ratings of a user in several rating systems.
+#[derive(Clone, Debug)]
+pub struct UserRatings {
+    user: User,
+    ratings: HashMap<RatingSystemKind, Summary>,
+}
+
+impl UserRatings {
+    pub fn user(&self) -> &User {
+        &self.user
+    }
+
+    pub fn ratings(&self) -> &HashMap<RatingSystemKind, Summary> {
+        &self.ratings
+    }
+}
+
+/// Reads all games of a group from the oldest to the latest together with
+/// all users that played in them.
+///
+/// Games keep the IDs users had when they played, which might have been
+/// merged into other users since. The returned games refer to the users they
+/// were merged into.
+async fn read_history(
     con: &mut Connection,
     group_id: &GroupId,
-    game_ids: &[GameId],
-) -> Result<Vec<Game>, Error> {
-    // Get below does not like it if the list requested is empty.
-    if game_ids.is_empty() {
-        return Ok(vec![]);
+) -> Result<(Vec<Game>, Vec<User>), Error> {
+    let game_ids: Vec<GameId> = con.zrange(games_key(group_id), 0, -1).await?;
+    // Games never will be deleted, so there is no race here.
+    let mut games = read_games(con, group_id, &game_ids).await?;
+
+    let user_ids = games
+        .iter()
+        .flat_map(|game| game.winner_ids.iter().chain(&game.loser_ids))
+        .cloned()
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect::<Vec<_>>();
+    let users = read_users(con, group_id, &user_ids).await?;
+    let root_ids = user_ids
+        .into_iter()
+        .zip(users.iter().map(|user| user.id.clone()))
+        .collect::<HashMap<_, _>>();
+    for game in &mut games {
+        for user_id in game.winner_ids.iter_mut().chain(&mut game.loser_ids) {
+            *user_id = root_ids[&*user_id].clone();
+        }
+        game.positions = game
+            .positions
+            .drain()
+            .map(|(user_id, position)| (root_ids[&user_id].clone(), position))
+            .collect();
+        game.weights = game
+            .weights
+            .drain()
+            .map(|(user_id, weight)| (root_ids[&user_id].clone(), weight))
+            .collect();
     }
 
-    Ok(redis::cmd("MGET")
-        .arg(
-            game_ids
-                .iter()
-                .map(|game_id| game_key(group_id, &game_id))
-                .collect::<Vec<_>>(),
-        )
-        .query_async::<_, Vec<RedisJson<Game>>>(con)
-        .await?
+    let users = users
         .into_iter()
-        .map(|RedisJson::<Game>(game)| game)
-        .collect())
+        .map(|user| (user.id.clone(), user))
+        .collect::<HashMap<_, _>>()
+        .into_values()
+        .collect();
+    Ok((games, users))
 }
 
-/// List all games.
+/// Rates the whole game history of a group with several rating systems side
+/// by side.
+///
+/// The ratings are computed once for every new game and, unlike the ratings
+/// of the users, ignore how much time passed between games. Users are
+/// ordered by their score in the first rating system.
 ///
 /// # Arguments
 ///
 /// * `group_id` ID of the group.
-/// * `before_game_id` start listing games before this optional game ID.
-pub async fn list_games(
+/// * `systems` rating systems to compute.
+pub async fn compare_ratings(
     con: &mut Connection,
     group_id: &GroupId,
-    before_game_id: &Option<GameId>,
-) -> Result<Vec<Game>, Error> {
-    let games_key = games_key(group_id);
-    let game_ids: Vec<GameId> = commit!(&mut *con, pipe, {
-        let before_game_rank = if let Some(game_id) = before_game_id {
-            let (_, rank): ((), isize) = redis::pipe()
-                .cmd("WATCH")
-                .arg(&games_key)
-                .ignore()
-                .zrevrank(&games_key, game_id.0.clone())
-                .query_async(con)
-                .await?;
-            rank + 1
-        } else {
-            0
+    systems: &[RatingSystemKind],
+) -> Result<Vec<UserRatings>, Error> {
+    let cache_key = |system: &RatingSystemKind| {
+        statistics_cache_key(group_id, &("ratings:".to_owned() + system.name()))
+    };
+    let game_count: usize = con.zcard(games_key(group_id)).await?;
+    let mut cached_summaries: Vec<Option<HashMap<UserId, Summary>>> = Vec::new();
+    for system in systems {
+        cached_summaries.push(read_cached_statistics(con, &cache_key(system), game_count).await?);
+    }
+
+    let mut summaries = Vec::new();
+    let users = if cached_summaries.iter().all(Option::is_some) {
+        summaries = systems
+            .iter()
+            .copied()
+            .zip(cached_summaries.into_iter().flatten())
+            .collect::<Vec<_>>();
+        // Every system rates the same users, the ones that played.
+        let user_ids = summaries
+            .first()
+            .map(|(_, summaries)| summaries.keys().cloned().collect::<Vec<_>>())
+            .unwrap_or_default();
+        read_users(con, group_id, &user_ids).await?
+    } else {
+        let (games, users) = read_history(con, group_id).await?;
+        let teams = games
+            .iter()
+            .map(|game| (game.winner_ids.clone(), game.loser_ids.clone()))
+            .collect::<Vec<_>>();
+        for (system, cached) in systems.iter().zip(cached_summaries) {
+            let system_summaries = match cached {
+                Some(cached) => cached,
+                None => {
+                    let replayed = system.replay(&true_skill(), &teams);
+                    write_cached_statistics(con, &cache_key(system), games.len(), &replayed)
+                        .await?;
+                    replayed
+                }
+            };
+            summaries.push((*system, system_summaries));
+        }
+        users
+    };
+
+    let mut ratings = users
+        .into_iter()
+        .map(|user| UserRatings {
+            ratings: summaries
+                .iter_mut()
+                .filter_map(|(system, summaries)| Some((*system, summaries.remove(&user.id)?)))
+                .collect(),
+            user,
+        })
+        .collect::<Vec<_>>();
+
+    if let Some(first) = systems.first() {
+        let score = |ratings: &UserRatings| {
+            ratings
+                .ratings
+                .get(first)
+                .map_or(f64::NEG_INFINITY, |summary| summary.score)
         };
+        ratings.sort_by(|a, b| by_descending_score(score(a), score(b)));
+    }
+    Ok(ratings)
+}
 
-        con.zrevrange(&games_key, before_game_rank, before_game_rank + 99)
-            .await
-            .map_err(|err| err.into())
-    })?;
-    // Games never will be deleted, so there is no race here.
-    read_games(con, group_id, &game_ids).await
+/// Skills of all users from the last smoothing pass over the game history.
+#[derive(Serialize, Deserialize, Debug)]
+struct SmoothedRatings {
+    /// When the pass ran.
+    datetime: chrono::DateTime<chrono::Utc>,
+    /// Smoothed skill of every user at their latest game.
+    players: HashMap<UserId, Player>,
 }
 
-/// Create a game and update all involved player scores.
+/// Smooths the skills of all users over the whole game history and stores
+/// them for the smoothed leaderboard.
 ///
-/// If a game with the same ID already exists, it will be overwritten.
+/// This reads and rates every game of the group a few times, so it is meant
+/// to run as a batch job.
 ///
 /// # Arguments
 ///
 /// * `group_id` ID of the group.
-/// * `game_id` ID of the game to create.
-/// * `winner_ids` user IDs of winning users.
-/// * `loser_ids` user IDs of losing users.
-/// * `datetime` when did the game take place.
-pub async fn create_game(
+/// * `datetime` when does the smoothing take place.
+pub async fn smooth_ratings(
     con: &mut Connection,
     group_id: &GroupId,
-    game_id: &GameId,
-    winner_ids: &[UserId],
-    loser_ids: &[UserId],
     datetime: chrono::DateTime<chrono::Utc>,
-) -> Result<Game, Error> {
-    let key = game_key(group_id, &game_id);
-    let game = Game {
-        id: game_id.clone(),
-        datetime,
-        winner_ids: winner_ids.to_owned(),
-        loser_ids: loser_ids.to_owned(),
-    };
+) -> Result<(), Error> {
+    let (games, _) = read_history(con, group_id).await?;
+    let players = smoothing::smooth(&games, &true_skill())
+        .into_iter()
+        .filter_map(|(user_id, skills)| {
+            let latest = skills.last()?;
+            let mut player = Player::new(latest.datetime);
+            player.set_skill(latest.skill, latest.datetime);
+            Some((user_id, player))
+        })
+        .collect();
+    con.set(
+        smoothed_ratings_key(group_id),
+        RedisJson(SmoothedRatings { datetime, players }),
+    )
+    .await
+    .map_err(|err| err.into())
+}
 
-    let timestamp_key = format!("{}", game.datetime.naive_utc().timestamp_millis());
+/// Ranks users by their skill from the last smoothing pass. Users that did
+/// not play before that pass are not ranked.
+///
+/// Returns when the pass ran, if it ever did, together with the ranked users.
+pub async fn get_smoothed_leaderboard(
+    con: &mut Connection,
+    group_id: &GroupId,
+    datetime: &chrono::DateTime<chrono::Utc>,
+) -> Result<(Option<chrono::DateTime<chrono::Utc>>, Vec<User>), Error> {
+    let smoothed: Option<RedisJson<SmoothedRatings>> =
+        con.get(smoothed_ratings_key(group_id)).await?;
+    let (smoothed_at, mut players) = match smoothed {
+        Some(RedisJson(smoothed)) => (smoothed.datetime, smoothed.players),
+        None => return Ok((None, vec![])),
+    };
 
-    commit!(&mut *con, pipe, {
-        // TODO(mkiefel): a lot of the users can be fetched in parallel.
-        let mut ctx = UserStoreCtx {
-            con,
-            group_id: group_id.clone(),
-            cache: HashMap::new(),
-        };
-        // Get user stats.
-        let mut winners = Vec::new();
-        for winner_id in winner_ids {
-            winners.push(merge::find(&mut ctx, winner_id.clone()).await?);
+    let user_ids = players.keys().cloned().collect::<Vec<_>>();
+    // Users that were merged since the pass count with the skill of the user
+    // they were merged into.
+    let mut users = Vec::new();
+    let mut seen = HashSet::new();
+    for (user_id, mut user) in user_ids
+        .iter()
+        .zip(read_users(con, group_id, &user_ids).await?)
+    {
+        if !seen.insert(user.id.clone()) {
+            continue;
         }
-        let mut losers = Vec::new();
-        for loser_id in loser_ids {
-            losers.push(merge::find(&mut ctx, loser_id.clone()).await?);
+        if let Some(player) = players.remove(&user.id).or_else(|| players.remove(user_id)) {
+            user.player = player;
         }
+        users.push(user);
+    }
+    users.sort_unstable_by(|user_a, user_b| {
+        by_descending_score(
+            map_score(&user_a.player, datetime),
+            map_score(&user_b.player, datetime),
+        )
+    });
+    Ok((Some(smoothed_at), users))
+}
 
-        // Reason about skills.
-        let true_skill = TrueSkill::new(Player::default_sigma() / 2.0, 0.0);
-        let (winner_updates, loser_updates) = true_skill.tree_pass(
-            &winners
-                .iter()
-                .map(|user| user.player.skill_at(&datetime).unwrap())
-                .collect::<Vec<_>>(),
-            &losers
-                .iter()
-                .map(|user| user.player.skill_at(&datetime).unwrap())
-                .collect::<Vec<_>>(),
-            GameResult::Won,
-        );
+/// Maximal number of players that can be split into teams at once.
+const MAX_BALANCED_PLAYERS: usize = 10;
 
-        // Update user stats.
-        for (winner, update) in winners.iter_mut().zip(winner_updates) {
-            winner.player.set_skill(
-                winner.player.skill_at(&datetime).unwrap().include(&update),
-                datetime,
-            );
-            merge::set(&mut ctx, winner.id.clone(), winner.clone()).await?;
-            pipe.zadd(
-                user_games_key(group_id, &winner.id),
-                &game.id.0,
-                &timestamp_key,
-            );
-        }
-        for (loser, update) in losers.iter_mut().zip(loser_updates) {
-            loser.player.set_skill(
-                loser.player.skill_at(&datetime).unwrap().include(&update),
-                datetime,
-            );
-            merge::set(&mut ctx, loser.id.clone(), loser.clone()).await?;
-            pipe.zadd(
-                user_games_key(group_id, &loser.id),
-                &game.id.0,
-                &timestamp_key,
-            );
-        }
+/// Splits users into the two teams with the fairest game.
+///
+/// Teams of two are lined up in positions based on the position skills of the
+/// users. Users that never played in a position count with their skill across
+/// all games.
+///
+/// # Arguments
+///
+/// * `group_id` ID of the group.
+/// * `user_ids` IDs of all users that want to play.
+/// * `datetime` when does the game take place.
+pub async fn balance_teams(
+    con: &mut Connection,
+    group_id: &GroupId,
+    user_ids: &[UserId],
+    datetime: &chrono::DateTime<chrono::Utc>,
+) -> Result<Lineup, Error> {
+    if user_ids.len() > MAX_BALANCED_PLAYERS {
+        return Err(Error::InvalidPlayerCount);
+    }
+    let mut seen = HashSet::new();
+    if let Some(user_id) = user_ids.iter().find(|user_id| !seen.insert(*user_id)) {
+        return Err(Error::InvalidGame(GameError::DuplicatePlayer {
+            user_id: user_id.clone(),
+        }));
+    }
 
-        ctx.append(&mut pipe);
-        pipe.set(&key, RedisJson(game.clone())).zadd(
-            games_key(group_id),
-            &game.id.0,
-            &timestamp_key,
-        );
-        Ok(())
-    })?;
-    Ok(game)
+    let users = read_users(con, group_id, user_ids).await?;
+    line_up(user_ids, users, datetime).ok_or(Error::InvalidPlayerCount)
 }
 
-fn map_score(user: &User, datetime: &chrono::DateTime<chrono::Utc>) -> f64 {
-    let (mu, sigma2) = user.player.skill_at(datetime).unwrap().to_mu_sigma2();
-    mu - 2.0 * sigma2.sqrt()
+/// Splits users into the two teams with the highest TrueSkill match quality.
+///
+/// # Arguments
+///
+/// * `user_ids` IDs of all users that want to play.
+/// * `users` the users of `user_ids` in the same order.
+/// * `datetime` when does the game take place.
+fn line_up(
+    user_ids: &[UserId],
+    users: Vec<User>,
+    datetime: &chrono::DateTime<chrono::Utc>,
+) -> Option<Lineup> {
+    let users = user_ids.iter().zip(users).collect::<HashMap<_, _>>();
+    let skill = |user_id: &UserId, position: Option<Position>| {
+        let user = &users[user_id];
+        let player = position
+            .and_then(|position| user.player_in(SkillKind::Position(position)))
+            .unwrap_or(&user.player);
+        player.skill_at(datetime).unwrap_or(*player.skill())
+    };
+    let true_skill = true_skill();
+    balance::balance(
+        user_ids,
+        skill,
+        |left_team, right_team| true_skill.match_quality(left_team, right_team),
+        |left_team, right_team| true_skill.win_probability(left_team, right_team),
+    )
 }
 
-fn group_key_prefix(group_id: &GroupId) -> String {
-    "group:".to_owned() + &group_id.0
+/// User waiting in the matchmaking queue.
+#[derive(Serialize, Clone, Debug)]
+pub struct QueueEntry {
+    user_id: UserId,
+    /// Since when is the user waiting.
+    since: chrono::DateTime<chrono::Utc>,
 }
 
-fn user_id_key(group_id: &GroupId) -> String {
-    group_key_prefix(group_id) + ":user.id"
+/// Marks a user as available for the next match. Users that already wait
+/// keep their place in the queue.
+pub async fn join_queue(
+    con: &mut Connection,
+    group_id: &GroupId,
+    user_id: &UserId,
+    datetime: &chrono::DateTime<chrono::Utc>,
+) -> Result<(), Error> {
+    // Makes sure that the user exists.
+    read_users(con, group_id, std::slice::from_ref(user_id)).await?;
+    redis::cmd("ZADD")
+        .arg(queue_key(group_id))
+        .arg("NX")
+        .arg(datetime.naive_utc().timestamp_millis())
+        .arg(&user_id.0)
+        .query_async(con)
+        .await
+        .map_err(|err| err.into())
 }
 
-fn user_name_index_key(group_id: &GroupId) -> String {
-    group_key_prefix(group_id) + ":user.name.index"
-}
+/// Removes a user from the matchmaking queue.
+pub async fn leave_queue(
+    con: &mut Connection,
+    group_id: &GroupId,
+    user_id: &UserId,
+) -> Result<(), Error> {
+    con.zrem(queue_key(group_id), &user_id.0)
+        .await
+        .map_err(|err| err.into())
+}
+
+/// Lists all users in the matchmaking queue, the longest waiting first.
+pub async fn list_queue(
+    con: &mut Connection,
+    group_id: &GroupId,
+) -> Result<Vec<QueueEntry>, Error> {
+    let entries: Vec<(UserId, i64)> = con.zrange_withscores(queue_key(group_id), 0, -1).await?;
+    entries
+        .into_iter()
+        .map(|(user_id, timestamp)| {
+            let since = chrono::Utc
+                .timestamp_millis_opt(timestamp)
+                .single()
+                .ok_or_else(|| {
+                    redis::RedisError::from((
+                        redis::ErrorKind::TypeError,
+                        "Queue entry has no valid timestamp",
+                        timestamp.to_string(),
+                    ))
+                })?;
+            Ok(QueueEntry { user_id, since })
+        })
+        .collect()
+}
+
+/// Proposes the next match between the users that waited longest and takes
+/// them out of the queue.
+///
+/// # Arguments
+///
+/// * `group_id` ID of the group.
+/// * `team_size` number of players per team.
+/// * `datetime` when does the game take place.
+pub async fn next_match(
+    con: &mut Connection,
+    group_id: &GroupId,
+    team_size: usize,
+    datetime: &chrono::DateTime<chrono::Utc>,
+) -> Result<Lineup, Error> {
+    let player_count = 2 * team_size;
+    if team_size == 0 || player_count > MAX_BALANCED_PLAYERS {
+        return Err(Error::InvalidPlayerCount);
+    }
+    let queue_key = queue_key(group_id);
+
+    commit!(&mut *con, pipe, {
+        redis::cmd("WATCH").arg(&queue_key).query_async(con).await?;
+        let user_ids: Vec<UserId> = con.zrange(&queue_key, 0, player_count as isize - 1).await?;
+        if user_ids.len() < player_count {
+            return Err(Error::NotEnoughQueued);
+        }
+
+        let mut ctx = UserStoreCtx {
+            con,
+            group_id: group_id.clone(),
+            cache: HashMap::new(),
+        };
+        let mut users = Vec::new();
+        for user_id in &user_ids {
+            users.push(merge::find(&mut ctx, user_id.clone()).await?);
+        }
+        ctx.append(&mut pipe);
+
+        let lineup = line_up(&user_ids, users, datetime).ok_or(Error::InvalidPlayerCount)?;
+        for user_id in &user_ids {
+            pipe.zrem(&queue_key, &user_id.0);
+        }
+        Ok(lineup)
+    })
+}
+
+/// Reads all games given by the vector of game IDs.
+pub async fn read_games(
+    con: &mut Connection,
+    group_id: &GroupId,
+    game_ids: &[GameId],
+) -> Result<Vec<Game>, Error> {
+    // Get below does not like it if the list requested is empty.
+    if game_ids.is_empty() {
+        return Ok(vec![]);
+    }
+
+    Ok(redis::cmd("MGET")
+        .arg(
+            game_ids
+                .iter()
+                .map(|game_id| game_key(group_id, &game_id))
+                .collect::<Vec<_>>(),
+        )
+        .query_async::<_, Vec<RedisJson<Game>>>(con)
+        .await?
+        .into_iter()
+        .map(|RedisJson::<Game>(game)| game)
+        .collect())
+}
+
+/// Returns the first and the last rank of the games on a page of games, from
+/// the latest game on. Returns `None` if the page is empty.
+///
+/// # Arguments
+///
+/// * `before_game_rank` rank of the first game that may be on the page.
+/// * `season_counts` number of games after a season and number of games in
+///   the season, if the page only lists the games of the season.
+fn game_page_ranks(
+    before_game_rank: isize,
+    season_counts: Option<(isize, isize)>,
+) -> Option<(isize, isize)> {
+    // Games are ordered by their timestamp, so the games of a season have
+    // consecutive ranks.
+    let (first_rank, last_rank) = match season_counts {
+        Some((later, within)) => (later, Some(later + within - 1)),
+        None => (0, None),
+    };
+
+    let start = before_game_rank.max(first_rank);
+    let stop = last_rank.map_or(start + 99, |last_rank| last_rank.min(start + 99));
+    if stop < start {
+        None
+    } else {
+        Some((start, stop))
+    }
+}
+
+/// List all games.
+///
+/// # Arguments
+///
+/// * `group_id` ID of the group.
+/// * `before_game_id` start listing games before this optional game ID.
+/// * `season` only list the games of this optional season.
+pub async fn list_games(
+    con: &mut Connection,
+    group_id: &GroupId,
+    before_game_id: &Option<GameId>,
+    season: Option<&Season>,
+) -> Result<Vec<Game>, Error> {
+    let games_key = games_key(group_id);
+    let game_ids: Vec<GameId> = commit!(&mut *con, pipe, {
+        redis::cmd("WATCH").arg(&games_key).query_async(con).await?;
+        let before_game_rank = if let Some(game_id) = before_game_id {
+            let rank: isize = con.zrevrank(&games_key, game_id.0.clone()).await?;
+            rank + 1
+        } else {
+            0
+        };
+
+        let season_counts = match season {
+            Some(season) => {
+                let start = season.start.naive_utc().timestamp_millis().to_string();
+                let (later, end) = match season.end {
+                    Some(end) => {
+                        let end = end.naive_utc().timestamp_millis();
+                        let later: isize = con.zcount(&games_key, end, "+inf").await?;
+                        (later, format!("({}", end))
+                    }
+                    None => (0, "+inf".to_owned()),
+                };
+                let within: isize = con.zcount(&games_key, start, end).await?;
+                Some((later, within))
+            }
+            None => None,
+        };
+
+        match game_page_ranks(before_game_rank, season_counts) {
+            Some((start, stop)) => con
+                .zrevrange(&games_key, start, stop)
+                .await
+                .map_err(|err| err.into()),
+            None => Ok(Vec::new()),
+        }
+    })?;
+    // Games never will be deleted, so there is no race here.
+    read_games(con, group_id, &game_ids).await
+}
+
+/// Reads the settings of a group. Groups that were never configured have the
+/// default settings.
+pub async fn read_group_settings(
+    con: &mut Connection,
+    group_id: &GroupId,
+) -> Result<GroupSettings, Error> {
+    let settings: Option<RedisJson<GroupSettings>> = con.get(group_settings_key(group_id)).await?;
+    Ok(settings
+        .map(|RedisJson(settings)| settings)
+        .unwrap_or_default())
+}
+
+/// Overwrites the settings of a group. Settings that are not valid are
+/// rejected.
+pub async fn write_group_settings(
+    con: &mut Connection,
+    group_id: &GroupId,
+    settings: &GroupSettings,
+) -> Result<(), Error> {
+    settings.validate()?;
+    con.set(group_settings_key(group_id), RedisJson(settings))
+        .await
+        .map_err(|err| err.into())
+}
+
+/// Competition period of a group.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Season {
+    /// Number of the season starting at 1.
+    number: u32,
+    name: String,
+    start: chrono::DateTime<chrono::Utc>,
+    /// End of the season or `None` while it is running.
+    end: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl Season {
+    pub fn number(&self) -> u32 {
+        self.number
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn start(&self) -> &chrono::DateTime<chrono::Utc> {
+        &self.start
+    }
+
+    pub fn end(&self) -> Option<&chrono::DateTime<chrono::Utc>> {
+        self.end.as_ref()
+    }
+}
+
+/// Position of a user on the leaderboard at the end of a season.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Standing {
+    user_id: UserId,
+    name: String,
+    skill: Message,
+    /// Position on the leaderboard starting at 1.
+    rank: usize,
+}
+
+impl Standing {
+    pub fn user_id(&self) -> &UserId {
+        &self.user_id
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn skill(&self) -> &Message {
+        &self.skill
+    }
+
+    pub fn rank(&self) -> usize {
+        self.rank
+    }
+}
+
+/// Reads all seasons of a group, the oldest first.
+pub async fn list_seasons(con: &mut Connection, group_id: &GroupId) -> Result<Vec<Season>, Error> {
+    let seasons: Option<RedisJson<Vec<Season>>> = con.get(seasons_key(group_id)).await?;
+    Ok(seasons
+        .map(|RedisJson(seasons)| seasons)
+        .unwrap_or_default())
+}
+
+/// Reads a season by its number.
+pub async fn find_season(
+    con: &mut Connection,
+    group_id: &GroupId,
+    number: u32,
+) -> Result<Season, Error> {
+    list_seasons(con, group_id)
+        .await?
+        .into_iter()
+        .find(|season| season.number == number)
+        .ok_or(Error::SeasonNotFound)
+}
+
+/// Ends the running season and starts a new one.
+///
+/// The leaderboard at the end of the running season gets archived. All skill
+/// beliefs of all users then move toward the one of a new player. Skill
+/// beliefs that were already estimated after the start of the new season stay
+/// as they are, which gets logged.
+///
+/// # Arguments
+///
+/// * `group_id` ID of the group.
+/// * `name` optional name of the season.
+/// * `reset` how far to move the skill beliefs, from 0 for keeping them to 1
+///   for starting over.
+/// * `datetime` when does the season start.
+pub async fn start_season(
+    con: &mut Connection,
+    group_id: &GroupId,
+    name: Option<String>,
+    reset: f64,
+    datetime: chrono::DateTime<chrono::Utc>,
+) -> Result<Season, Error> {
+    if !(0.0..=1.0).contains(&reset) {
+        return Err(Error::InvalidSeasonReset);
+    }
+    let seasons_key = seasons_key(group_id);
+    let user_id_key = user_id_key(group_id);
+
+    let (season, kept) = commit!(&mut *con, pipe, {
+        redis::cmd("WATCH")
+            .arg(&seasons_key)
+            .arg(&user_id_key)
+            .query_async(con)
+            .await?;
+        let seasons: Option<RedisJson<Vec<Season>>> = con.get(&seasons_key).await?;
+        let mut seasons = seasons
+            .map(|RedisJson(seasons)| seasons)
+            .unwrap_or_default();
+        let user_ids: Vec<UserId> = con.smembers(&user_id_key).await?;
+
+        let mut ctx = UserStoreCtx {
+            con,
+            group_id: group_id.clone(),
+            cache: HashMap::new(),
+        };
+        let mut users: Vec<User> = Vec::new();
+        for user_id in user_ids {
+            let user = merge::find(&mut ctx, user_id).await?;
+            if users.iter().all(|known| known.id != user.id) {
+                users.push(user);
+            }
+        }
+
+        // Archive the leaderboard of the running season.
+        if let Some(running) = seasons.last_mut() {
+            running.end = Some(datetime);
+            let mut scored_users = users
+                .iter()
+                .filter_map(|user| Some((user, user.player.skill_at(&datetime)?)))
+                .collect::<Vec<_>>();
+            scored_users.sort_by(|(_, skill_a), (_, skill_b)| {
+                by_descending_score(conservative_score(skill_a), conservative_score(skill_b))
+            });
+            let standings = scored_users
+                .into_iter()
+                .enumerate()
+                .map(|(index, (user, skill))| Standing {
+                    user_id: user.id.clone(),
+                    name: user.name.clone(),
+                    skill,
+                    rank: index + 1,
+                })
+                .collect::<Vec<_>>();
+            pipe.set(
+                season_standings_key(group_id, running.number),
+                RedisJson(standings),
+            );
+        }
+
+        let mut kept = 0;
+        for mut user in users {
+            let mut regress = |player: &mut Player| match player.regress(datetime, reset) {
+                Some(regressed) => *player = regressed,
+                None => kept += 1,
+            };
+            regress(&mut user.player);
+            user.mode_players.values_mut().for_each(&mut regress);
+            user.position_players.values_mut().for_each(&mut regress);
+            pipe.zadd(
+                leaderboard_index_key(group_id),
+                &user.id.0,
+                index_score(&user.player),
+            );
+            merge::set(&mut ctx, user.id.clone(), user).await?;
+        }
+        ctx.append(&mut pipe);
+
+        let number = seasons.last().map_or(1, |season| season.number + 1);
+        let season = Season {
+            number,
+            name: name.clone().unwrap_or_else(|| format!("Season {}", number)),
+            start: datetime,
+            end: None,
+        };
+        seasons.push(season.clone());
+        pipe.set(&seasons_key, RedisJson(seasons));
+        Ok((season, kept))
+    })?;
+    if kept > 0 {
+        rocket::warn!(
+            "season {} kept {} skill beliefs that were estimated after its start",
+            season.number,
+            kept
+        );
+    }
+    Ok(season)
+}
+
+/// Reads the archived leaderboard at the end of a season.
+pub async fn read_season_standings(
+    con: &mut Connection,
+    group_id: &GroupId,
+    season: &Season,
+) -> Result<Vec<Standing>, Error> {
+    let standings: Option<RedisJson<Vec<Standing>>> = con
+        .get(season_standings_key(group_id, season.number))
+        .await?;
+    Ok(standings
+        .map(|RedisJson(standings)| standings)
+        .unwrap_or_default())
+}
+
+/// Finds a user that takes part in a game.
+async fn find_player<C>(ctx: &mut C, user_id: &UserId) -> Result<User, Error>
+where
+    C: merge::MergeCtx<Index = UserId, Item = User> + std::marker::Send,
+{
+    merge::find(ctx, user_id.clone())
+        .await
+        .map_err(|err| match err {
+            merge::Error::MissingEntryError(user_id) => {
+                Error::InvalidGame(GameError::UnknownUser { user_id })
+            }
+            err => err.into(),
+        })
+}
+
+/// Skill of a user before and after a game.
+#[derive(Serialize, Clone, Deserialize, Debug)]
+pub struct SkillChange {
+    user_id: UserId,
+    before: Message,
+    after: Message,
+}
+
+impl SkillChange {
+    pub fn user_id(&self) -> &UserId {
+        &self.user_id
+    }
+
+    pub fn before(&self) -> &Message {
+        &self.before
+    }
+
+    pub fn after(&self) -> &Message {
+        &self.after
+    }
+}
+
+/// Learned advantage in performance of the home side of a table.
+#[derive(Serialize, Clone, Deserialize, Debug)]
+pub struct SideAdvantage {
+    /// Table the advantage is of. Games without table share one advantage.
+    table: Option<String>,
+    advantage: Message,
+    /// Number of games the advantage was learned from.
+    games: usize,
+}
+
+impl SideAdvantage {
+    /// Makes the advantage of a table without any games. Tables start out
+    /// without advantage, but it may grow up to about a skill sigma.
+    fn new(table: Option<String>) -> Self {
+        SideAdvantage {
+            table,
+            advantage: Message::from_mu_sigma2(0.0, (Player::default_sigma() / 2.0).powi(2)),
+            games: 0,
+        }
+    }
+
+    pub fn table(&self) -> Option<&str> {
+        self.table.as_deref()
+    }
+
+    pub fn advantage(&self) -> &Message {
+        &self.advantage
+    }
+
+    pub fn games(&self) -> usize {
+        self.games
+    }
+}
+
+/// Reads the advantages of all tables of a group by the name of their table
+/// and watches them for changes as part of a transaction.
+async fn watch_side_advantages(
+    con: &mut Connection,
+    group_id: &GroupId,
+) -> Result<HashMap<String, SideAdvantage>, Error> {
+    let side_advantages_key = side_advantages_key(group_id);
+    redis::cmd("WATCH")
+        .arg(&side_advantages_key)
+        .query_async(con)
+        .await?;
+    let side_advantages: HashMap<String, RedisJson<SideAdvantage>> =
+        con.hgetall(&side_advantages_key).await?;
+    Ok(side_advantages
+        .into_iter()
+        .map(|(table, RedisJson(side_advantage))| (table, side_advantage))
+        .collect())
+}
+
+/// Writes the advantages of the tables that games with known sides were
+/// played on as part of a transaction.
+fn write_side_advantages(
+    pipe: &mut redis::Pipeline,
+    group_id: &GroupId,
+    side_advantages: &HashMap<String, SideAdvantage>,
+    games: &[&Game],
+) {
+    let side_advantages_key = side_advantages_key(group_id);
+    let tables = games
+        .iter()
+        .filter(|game| game.winner_side.is_some())
+        .map(|game| game.table.clone().unwrap_or_default())
+        .collect::<HashSet<_>>();
+    for table in tables {
+        if let Some(side_advantage) = side_advantages.get(&table) {
+            pipe.hset(&side_advantages_key, &table, RedisJson(side_advantage));
+        }
+    }
+}
+
+/// Skill of every player before and after a game together with the advantage
+/// of the home side after the game, if the sides are known.
+type RatedPlayers = (Vec<(Message, Message)>, Option<Message>);
+
+/// Rates a game between two teams.
+///
+/// # Arguments
+///
+/// * `user_ids` IDs of all users, winners first.
+/// * `players` players of all users in the same order.
+/// * `weights` fractions of the game all users played in the same order.
+/// * `winner_count` number of winners.
+/// * `advantage` side of the winners together with the advantage of the home
+///   side, if the sides of the game are known.
+/// * `datetime` when did the game take place.
+fn rate_players(
+    user_ids: &[UserId],
+    players: &[Player],
+    weights: &[f64],
+    winner_count: usize,
+    advantage: Option<(Side, Message)>,
+    datetime: &chrono::DateTime<chrono::Utc>,
+) -> Result<RatedPlayers, Error> {
+    // The temporal model of the players can only move forward in time.
+    let skills = user_ids
+        .iter()
+        .zip(players)
+        .map(|(user_id, player)| {
+            player.skill_at(datetime).ok_or_else(|| {
+                Error::InvalidGame(GameError::BeforeLastGame {
+                    user_id: user_id.clone(),
+                })
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // Reason about skills, weighted by how much of the game everyone played.
+    let weighted_skills = weights.iter().copied().zip(skills.iter().copied());
+    let winners = weighted_skills
+        .clone()
+        .take(winner_count)
+        .collect::<Vec<_>>();
+    let losers = weighted_skills.skip(winner_count).collect::<Vec<_>>();
+    let (winners_after, losers_after, advantage_after) = match advantage {
+        // The advantage adds to the performance of the home team.
+        Some((side, advantage)) => {
+            let offsets = match side {
+                Side::Home => [Some(advantage), None],
+                Side::Away => [None, Some(advantage)],
+            };
+            let (winners_after, losers_after, offsets_after) =
+                true_skill().rate_with_offsets(&winners, &losers, offsets);
+            let advantage_after = offsets_after.iter().flatten().next().copied();
+            (winners_after, losers_after, advantage_after)
+        }
+        None => {
+            let (winners_after, losers_after) = true_skill().rate_weighted(&winners, &losers);
+            (winners_after, losers_after, None)
+        }
+    };
+
+    Ok((
+        skills
+            .iter()
+            .copied()
+            .zip(winners_after.into_iter().chain(losers_after))
+            .collect(),
+        advantage_after,
+    ))
+}
+
+/// Updates the scores of all players of a game and writes the game as part of
+/// a transaction.
+///
+/// Records how the skills of all players of the game changed in the game.
+/// Games with known sides also update the advantage of the home side of their
+/// table.
+async fn add_game<C>(
+    ctx: &mut UserStoreCtx<'_, C>,
+    pipe: &mut redis::Pipeline,
+    game: &mut Game,
+    side_advantages: &mut HashMap<String, SideAdvantage>,
+) -> Result<(), Error>
+where
+    C: redis::aio::ConnectionLike + std::marker::Send,
+{
+    let group_id = ctx.group_id.clone();
+    let datetime = game.datetime;
+    let timestamp_key = format!("{}", datetime.naive_utc().timestamp_millis());
+
+    // TODO(mkiefel): a lot of the users can be fetched in parallel.
+    // Get user stats.
+    let user_ids = game
+        .winner_ids
+        .iter()
+        .chain(&game.loser_ids)
+        .cloned()
+        .collect::<Vec<_>>();
+    let mut users = Vec::new();
+    for user_id in &user_ids {
+        users.push(find_player(ctx, user_id).await?);
+    }
+    let weights = user_ids
+        .iter()
+        .map(|user_id| game.weight(user_id))
+        .collect::<Vec<_>>();
+    let winner_count = game.winner_ids.len();
+
+    // The ratings across all games are always updated, the ones of the game
+    // mode only if there is one.
+    let players = users
+        .iter()
+        .map(|user| user.player.clone())
+        .collect::<Vec<_>>();
+    let table = game.table.clone().unwrap_or_default();
+    let advantage = game.winner_side.map(|winner_side| {
+        let side_advantage = side_advantages
+            .entry(table.clone())
+            .or_insert_with(|| SideAdvantage::new(game.table.clone()));
+        (winner_side, side_advantage.advantage)
+    });
+    // Only the ratings across all games learn the advantage, the others only
+    // account for it.
+    let (skills, advantage_after) = rate_players(
+        &user_ids,
+        &players,
+        &weights,
+        winner_count,
+        advantage,
+        &datetime,
+    )?;
+    if let (Some(advantage_after), Some(side_advantage)) =
+        (advantage_after, side_advantages.get_mut(&table))
+    {
+        side_advantage.advantage = advantage_after;
+        side_advantage.games += 1;
+    }
+    let mode_skills = match &game.mode {
+        Some(mode) => {
+            let mode_players = users
+                .iter()
+                .map(|user| {
+                    user.mode_players
+                        .get(mode)
+                        .cloned()
+                        .unwrap_or_else(|| Player::new(datetime))
+                })
+                .collect::<Vec<_>>();
+            Some(
+                rate_players(
+                    &user_ids,
+                    &mode_players,
+                    &weights,
+                    winner_count,
+                    advantage,
+                    &datetime,
+                )?
+                .0,
+            )
+        }
+        None => None,
+    };
+
+    let positions = user_ids
+        .iter()
+        .map(|user_id| game.positions.get(user_id).copied())
+        .collect::<Option<Vec<_>>>();
+    let position_skills = match &positions {
+        Some(positions) => {
+            let position_players = users
+                .iter()
+                .zip(positions)
+                .map(|(user, position)| {
+                    user.position_players
+                        .get(position)
+                        .cloned()
+                        .unwrap_or_else(|| Player::new(datetime))
+                })
+                .collect::<Vec<_>>();
+            Some(
+                rate_players(
+                    &user_ids,
+                    &position_players,
+                    &weights,
+                    winner_count,
+                    advantage,
+                    &datetime,
+                )?
+                .0,
+            )
+        }
+        None => None,
+    };
+
+    // Update user stats.
+    let mut skill_changes = Vec::new();
+    for (index, mut user) in users.into_iter().enumerate() {
+        let (before, after) = skills[index];
+        user.player.set_skill(after, datetime);
+        pipe.zadd(
+            leaderboard_index_key(&group_id),
+            &user.id.0,
+            index_score(&user.player),
+        );
+        if let (Some(mode), Some(mode_skills)) = (&game.mode, &mode_skills) {
+            let mut mode_player = Player::new(datetime);
+            mode_player.set_skill(mode_skills[index].1, datetime);
+            user.mode_players.insert(mode.clone(), mode_player);
+        }
+        if let (Some(positions), Some(position_skills)) = (&positions, &position_skills) {
+            let mut position_player = Player::new(datetime);
+            position_player.set_skill(position_skills[index].1, datetime);
+            user.position_players
+                .insert(positions[index], position_player);
+        }
+        merge::set(ctx, user.id.clone(), user.clone()).await?;
+        pipe.zadd(
+            user_games_key(&group_id, &user.id),
+            &game.id.0,
+            &timestamp_key,
+        )
+        .zadd(
+            user_activity_key(&group_id),
+            &user.id.0,
+            activity_score(&datetime),
+        );
+        skill_changes.push(SkillChange {
+            user_id: user.id,
+            before,
+            after,
+        });
+    }
+
+    game.skill_changes = skill_changes;
+    pipe.set(game_key(&group_id, &game.id), RedisJson(&*game))
+        .zadd(games_key(&group_id), &game.id.0, &timestamp_key);
+    Ok(())
+}
+
+/// Create a game and update all involved player scores.
+///
+/// If a game with the same ID already exists, it will be overwritten. The game
+/// has to be valid and follow the game rules of the group.
+///
+/// # Arguments
+///
+/// * `group_id` ID of the group.
+/// * `game` game to create.
+/// * `idempotency_key` optional key that identifies retries. If the same game
+///   was already created with the same key, that game is returned instead
+///   and no scores change. Using the key for a different game is an error.
+///
+/// Returns the created game together with the rating changes of its players.
+pub async fn create_game(
+    con: &mut Connection,
+    group_id: &GroupId,
+    mut game: Game,
+    idempotency_key: Option<&str>,
+) -> Result<(Game, Vec<RatingChange>), Error> {
+    let settings = read_group_settings(con, group_id).await?;
+    prepare_game(&settings, &mut game)?;
+    let idempotency_key =
+        idempotency_key.map(|idempotency_key| game_idempotency_key(group_id, idempotency_key));
+    let request_hash = request_hash(&game);
+
+    let (game, rating_changes, created) = commit!(&mut *con, pipe, {
+        if let Some(idempotency_key) = &idempotency_key {
+            // Check whether this is a retry of an already created game.
+            redis::cmd("WATCH")
+                .arg(idempotency_key)
+                .query_async(con)
+                .await?;
+            let created_game: Option<RedisJson<CreatedGame>> = con.get(idempotency_key).await?;
+            if let Some(RedisJson(created_game)) = created_game {
+                if created_game.request_hash != request_hash {
+                    return Err(Error::IdempotencyKeyReused);
+                }
+                let RedisJson::<Game>(game) =
+                    con.get(game_key(group_id, &created_game.game_id)).await?;
+                return Ok((game, created_game.rating_changes, false));
+            }
+        }
+
+        let mut side_advantages = watch_side_advantages(con, group_id).await?;
+        let mut ctx = UserStoreCtx {
+            con,
+            group_id: group_id.clone(),
+            cache: HashMap::new(),
+        };
+        add_game(&mut ctx, &mut pipe, &mut game, &mut side_advantages).await?;
+
+        ctx.append(&mut pipe);
+        write_side_advantages(&mut pipe, group_id, &side_advantages, &[&game]);
+        add_to_leagues(con, &mut pipe, group_id, &[&game]).await?;
+        let rating_changes = rank_skill_changes(con, group_id, &game.skill_changes).await?;
+        if let Some(idempotency_key) = &idempotency_key {
+            pipe.set_ex(
+                idempotency_key,
+                RedisJson(CreatedGame {
+                    request_hash: request_hash.clone(),
+                    game_id: game.id.clone(),
+                    rating_changes: rating_changes.clone(),
+                }),
+                IDEMPOTENCY_KEY_SECONDS,
+            );
+        }
+        Ok((game.clone(), rating_changes, true))
+    })?;
+    if created {
+        publish_event(
+            con,
+            group_id,
+            &GroupEvent::GameCreated { game: game.clone() },
+        )
+        .await;
+    }
+    Ok((game, rating_changes))
+}
+
+/// Hashes everything about a game a client asks for, so that a retry can be
+/// told apart from a different game under the same idempotency key.
+fn request_hash(game: &Game) -> String {
+    let request = (
+        &game.winner_ids,
+        &game.loser_ids,
+        game.positions.iter().collect::<BTreeMap<_, _>>(),
+        game.weights.iter().collect::<BTreeMap<_, _>>(),
+        &game.table,
+        game.winner_side,
+    );
+    Sha256::digest(serde_json::to_vec(&request).expect("games serialize to JSON"))
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Checks that a game follows the rules of the group and assigns its game
+/// mode.
+fn prepare_game(settings: &GroupSettings, game: &mut Game) -> Result<(), GameError> {
+    settings
+        .game_rules
+        .validate(&game.winner_ids, &game.loser_ids)?;
+    game.validate_positions()?;
+    game.validate_weights()?;
+    if game
+        .table
+        .as_deref()
+        .is_some_and(|table| table.trim().is_empty())
+    {
+        game.table = None;
+    }
+    game.mode = settings
+        .game_mode(&game.winner_ids, &game.loser_ids)
+        .map(|mode| mode.name.clone());
+    Ok(())
+}
+
+/// Creates several games at once in chronological order and updates all
+/// involved player scores.
+///
+/// Either all games are valid and get created or none of them. Errors about
+/// invalid games report the position of the game in `games`. Games cannot be
+/// inserted into the history: every game has to take place after the latest
+/// recorded skill of each of its players.
+///
+/// Returns the created games in the order of `games` together with the
+/// overall rating change of every involved user.
+pub async fn create_games(
+    con: &mut Connection,
+    group_id: &GroupId,
+    games: &[Game],
+) -> Result<(Vec<Game>, Vec<RatingChange>), Error> {
+    let settings = read_group_settings(con, group_id).await?;
+    let mut games = games.to_vec();
+    for (index, game) in games.iter_mut().enumerate() {
+        prepare_game(&settings, game).map_err(|err| Error::InvalidBatchGame(index, err))?;
+    }
+
+    let mut order = (0..games.len()).collect::<Vec<_>>();
+    order.sort_by_key(|index| games[*index].datetime);
+
+    let (games, rating_changes) = commit!(&mut *con, pipe, {
+        let mut side_advantages = watch_side_advantages(con, group_id).await?;
+        let mut ctx = UserStoreCtx {
+            con,
+            group_id: group_id.clone(),
+            cache: HashMap::new(),
+        };
+
+        let mut games = games.clone();
+        // Keeps the skill of each user before its first and after its last
+        // game.
+        let mut skill_changes: Vec<SkillChange> = Vec::new();
+        for index in &order {
+            let game = &mut games[*index];
+            match add_game(&mut ctx, &mut pipe, game, &mut side_advantages).await {
+                Ok(()) => (),
+                // The history of a user cannot be rewritten.
+                Err(Error::InvalidGame(GameError::BeforeLastGame { user_id })) => {
+                    let user = find_player(&mut ctx, &user_id).await?;
+                    let timestamp = user.player.datetime().timestamp_millis();
+                    return Err(Error::InvalidBatchGame(
+                        *index,
+                        GameError::BackdatedGame { user_id, timestamp },
+                    ));
+                }
+                Err(Error::InvalidGame(err)) => return Err(Error::InvalidBatchGame(*index, err)),
+                Err(err) => return Err(err),
+            }
+            for skill_change in &game.skill_changes {
+                match skill_changes
+                    .iter_mut()
+                    .find(|known| known.user_id == skill_change.user_id)
+                {
+                    Some(known) => known.after = skill_change.after,
+                    None => skill_changes.push(skill_change.clone()),
+                }
+            }
+        }
+
+        ctx.append(&mut pipe);
+        let ordered_games = order.iter().map(|index| &games[*index]).collect::<Vec<_>>();
+        write_side_advantages(&mut pipe, group_id, &side_advantages, &ordered_games);
+        add_to_leagues(con, &mut pipe, group_id, &ordered_games).await?;
+        let rating_changes = rank_skill_changes(con, group_id, &skill_changes).await?;
+        Ok((games, rating_changes))
+    })?;
+    for index in order {
+        publish_event(
+            con,
+            group_id,
+            &GroupEvent::GameCreated {
+                game: games[index].clone(),
+            },
+        )
+        .await;
+    }
+    Ok((games, rating_changes))
+}
+
+/// Checks that every two teams of a tournament or a league could play a game
+/// that follows the rules of the group.
+fn validate_team_pairings(
+    settings: &GroupSettings,
+    teams: &[Vec<UserId>],
+) -> Result<(), GameError> {
+    for (index, winner_ids) in teams.iter().enumerate() {
+        for loser_ids in &teams[index + 1..] {
+            settings.game_rules.validate(winner_ids, loser_ids)?;
+        }
+    }
+    Ok(())
+}
+
+/// Creates a knockout tournament. Entrants are seeded by the mean
+/// conservative score of their players, the strongest first.
+///
+/// # Arguments
+///
+/// * `group_id` ID of the group.
+/// * `tournament_id` ID of the tournament to create.
+/// * `name` name of the tournament.
+/// * `format` how entrants drop out.
+/// * `entrants` user IDs of the players of every entrant.
+/// * `datetime` point in time of the scores used for seeding.
+pub async fn create_tournament(
+    con: &mut Connection,
+    group_id: &GroupId,
+    tournament_id: TournamentId,
+    name: String,
+    format: Format,
+    mut entrants: Vec<Vec<UserId>>,
+    datetime: &chrono::DateTime<chrono::Utc>,
+) -> Result<Tournament, Error> {
+    Tournament::validate_entrants(&entrants)?;
+    let settings = read_group_settings(con, group_id).await?;
+    validate_team_pairings(&settings, &entrants)?;
+    let mut scores = Vec::new();
+    for user_ids in &entrants {
+        let users = read_users(con, group_id, user_ids).await?;
+        let score = users
+            .iter()
+            .map(|user| map_score(&user.player, datetime))
+            .sum::<f64>()
+            / users.len() as f64;
+        scores.push(score);
+    }
+    let mut order = (0..entrants.len()).collect::<Vec<_>>();
+    order.sort_by(|a, b| by_descending_score(scores[*a], scores[*b]));
+    let mut seeded = Vec::new();
+    for index in order {
+        seeded.push(std::mem::take(&mut entrants[index]));
+    }
+
+    let tournament = Tournament::new(tournament_id, name, format, seeded)?;
+    con.hset(
+        tournaments_key(group_id),
+        &tournament.id().0,
+        RedisJson(&tournament),
+    )
+    .await?;
+    Ok(tournament)
+}
+
+/// Lists all tournaments of a group.
+pub async fn list_tournaments(
+    con: &mut Connection,
+    group_id: &GroupId,
+) -> Result<Vec<Tournament>, Error> {
+    let tournaments: Vec<RedisJson<Tournament>> = con.hvals(tournaments_key(group_id)).await?;
+    Ok(tournaments
+        .into_iter()
+        .map(|RedisJson(tournament)| tournament)
+        .collect())
+}
+
+/// Reads a tournament with its bracket.
+pub async fn read_tournament(
+    con: &mut Connection,
+    group_id: &GroupId,
+    tournament_id: &TournamentId,
+) -> Result<Tournament, Error> {
+    let tournament: Option<RedisJson<Tournament>> = con
+        .hget(tournaments_key(group_id), &tournament_id.0)
+        .await?;
+    tournament
+        .map(|RedisJson(tournament)| tournament)
+        .ok_or(Error::TournamentNotFound)
+}
+
+/// Records the result of a tournament match as a game.
+///
+/// The game follows the rules of the group and updates the scores of all
+/// players like any other game. Either both the game gets created and the
+/// bracket moves on or nothing changes.
+///
+/// # Arguments
+///
+/// * `group_id` ID of the group.
+/// * `tournament_id` ID of the tournament.
+/// * `match_index` index of the match in the bracket.
+/// * `winner` side of the match that won.
+/// * `game_id` ID of the game to create.
+/// * `datetime` when did the game take place.
+///
+/// Returns the tournament and the game together with the rating changes of
+/// its players.
+pub async fn record_tournament_match(
+    con: &mut Connection,
+    group_id: &GroupId,
+    tournament_id: &TournamentId,
+    match_index: usize,
+    winner: usize,
+    game_id: GameId,
+    datetime: chrono::DateTime<chrono::Utc>,
+) -> Result<(Tournament, Game, Vec<RatingChange>), Error> {
+    let settings = read_group_settings(con, group_id).await?;
+    let tournaments_key = tournaments_key(group_id);
+
+    let (tournament, game, rating_changes) = commit!(&mut *con, pipe, {
+        redis::cmd("WATCH")
+            .arg(&tournaments_key)
+            .query_async(con)
+            .await?;
+        let tournament: Option<RedisJson<Tournament>> =
+            con.hget(&tournaments_key, &tournament_id.0).await?;
+        let mut tournament = tournament
+            .map(|RedisJson(tournament)| tournament)
+            .ok_or(Error::TournamentNotFound)?;
+        let (winner_ids, loser_ids) = tournament.record(match_index, winner, game_id.clone())?;
+
+        let mut game = Game::new(game_id.clone(), datetime, winner_ids, loser_ids);
+        prepare_game(&settings, &mut game)?;
+        let mut side_advantages = watch_side_advantages(con, group_id).await?;
+        let mut ctx = UserStoreCtx {
+            con,
+            group_id: group_id.clone(),
+            cache: HashMap::new(),
+        };
+        add_game(&mut ctx, &mut pipe, &mut game, &mut side_advantages).await?;
+
+        ctx.append(&mut pipe);
+        write_side_advantages(&mut pipe, group_id, &side_advantages, &[&game]);
+        add_to_leagues(con, &mut pipe, group_id, &[&game]).await?;
+        let rating_changes = rank_skill_changes(con, group_id, &game.skill_changes).await?;
+        pipe.hset(&tournaments_key, &tournament_id.0, RedisJson(&tournament));
+        Ok((tournament, game, rating_changes))
+    })?;
+    publish_event(
+        con,
+        group_id,
+        &GroupEvent::GameCreated { game: game.clone() },
+    )
+    .await;
+    Ok((tournament, game, rating_changes))
+}
+
+/// Creates a round-robin league and schedules its fixtures. Every two
+/// participants have to be able to play a game that follows the rules of the
+/// group.
+///
+/// # Arguments
+///
+/// * `group_id` ID of the group.
+/// * `league_id` ID of the league to create.
+/// * `name` name of the league.
+/// * `participants` user IDs of the players of every participant.
+/// * `scoring` points for wins and losses.
+pub async fn create_league(
+    con: &mut Connection,
+    group_id: &GroupId,
+    league_id: LeagueId,
+    name: String,
+    participants: Vec<Vec<UserId>>,
+    scoring: Scoring,
+) -> Result<League, Error> {
+    let league = League::new(league_id, name, participants, scoring)?;
+    let settings = read_group_settings(con, group_id).await?;
+    validate_team_pairings(&settings, league.participants())?;
+    // Makes sure that all participants exist.
+    read_users(con, group_id, &league.participants().concat()).await?;
+    let mut pipe = redis::pipe();
+    pipe.atomic()
+        .hset(leagues_key(group_id), &league.id().0, RedisJson(&league))
+        .ignore();
+    for user_id in league.participants().iter().flatten() {
+        pipe.sadd(user_leagues_key(group_id, user_id), &league.id().0)
+            .ignore();
+    }
+    pipe.query_async::<_, ()>(con).await?;
+    Ok(league)
+}
+
+/// Lists all leagues of a group.
+pub async fn list_leagues(con: &mut Connection, group_id: &GroupId) -> Result<Vec<League>, Error> {
+    let leagues: Vec<RedisJson<League>> = con.hvals(leagues_key(group_id)).await?;
+    Ok(leagues
+        .into_iter()
+        .map(|RedisJson(league)| league)
+        .collect())
+}
+
+/// Reads a league with all its fixtures.
+pub async fn read_league(
+    con: &mut Connection,
+    group_id: &GroupId,
+    league_id: &LeagueId,
+) -> Result<League, Error> {
+    let league: Option<RedisJson<League>> = con.hget(leagues_key(group_id), &league_id.0).await?;
+    league
+        .map(|RedisJson(league)| league)
+        .ok_or(Error::LeagueNotFound)
+}
+
+/// Assigns games to the open league fixtures between their teams as part of
+/// a transaction. Only the open leagues of the players are read, and leagues
+/// that finish are no longer open for their players.
+///
+/// # Arguments
+///
+/// * `group_id` ID of the group.
+/// * `games` games in chronological order.
+async fn add_to_leagues(
+    con: &mut Connection,
+    pipe: &mut redis::Pipeline,
+    group_id: &GroupId,
+    games: &[&Game],
+) -> Result<(), Error> {
+    // Both teams of a league game are participants of the league, so the open
+    // leagues of any one player of a game include all leagues of the game.
+    let user_leagues_keys = games
+        .iter()
+        .filter_map(|game| game.winner_ids.first())
+        .map(|user_id| user_leagues_key(group_id, user_id))
+        .collect::<HashSet<_>>();
+    if user_leagues_keys.is_empty() {
+        return Ok(());
+    }
+    redis::cmd("WATCH")
+        .arg(user_leagues_keys.iter().collect::<Vec<_>>())
+        .query_async(con)
+        .await?;
+    let league_ids: HashSet<String> = con
+        .sunion(user_leagues_keys.into_iter().collect::<Vec<_>>())
+        .await?;
+    if league_ids.is_empty() {
+        return Ok(());
+    }
+
+    let leagues_key = leagues_key(group_id);
+    redis::cmd("WATCH")
+        .arg(&leagues_key)
+        .query_async(con)
+        .await?;
+    let league_ids = league_ids.into_iter().collect::<Vec<_>>();
+    let leagues: Vec<Option<RedisJson<League>>> = redis::cmd("HMGET")
+        .arg(&leagues_key)
+        .arg(&league_ids)
+        .query_async(con)
+        .await?;
+    for RedisJson(mut league) in leagues.into_iter().flatten() {
+        let mut recorded = false;
+        for game in games {
+            recorded |= league.record(&game.id, &game.winner_ids, &game.loser_ids);
+        }
+        if !recorded {
+            continue;
+        }
+        pipe.hset(&leagues_key, &league.id().0, RedisJson(&league));
+        if league.is_finished() {
+            for user_id in league.participants().iter().flatten() {
+                pipe.srem(user_leagues_key(group_id, user_id), &league.id().0);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Something that happened in a group.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum GroupEvent {
+    /// A game was created and the skills of its players were updated.
+    GameCreated { game: Game },
+    /// A user was created.
+    UserCreated { user: User },
+}
+
+impl GroupEvent {
+    /// Name of the kind of the event.
+    pub fn name(&self) -> &'static str {
+        match self {
+            GroupEvent::GameCreated { .. } => "game_created",
+            GroupEvent::UserCreated { .. } => "user_created",
+        }
+    }
+}
+
+/// Notifies all subscribers of a group about an event.
+///
+/// Delivery is best effort. At this point the event already happened, so a
+/// failure to publish is only logged.
+async fn publish_event(con: &mut Connection, group_id: &GroupId, event: &GroupEvent) {
+    let result: redis::RedisResult<()> = con
+        .publish(group_events_key(group_id), RedisJson(event))
+        .await;
+    if let Err(err) = result {
+        rocket::error!("failed to publish {:?}: {:?}", event.name(), err);
+    }
+    if let Err(err) = enqueue_webhook_deliveries(con, group_id, event).await {
+        rocket::error!(
+            "failed to enqueue webhooks for {:?}: {:?}",
+            event.name(),
+            err
+        );
+    }
+}
+
+/// Subscribes to all events of a group.
+///
+/// Takes ownership of the connection as it can only be used for the
+/// subscription from now on.
+pub async fn subscribe_events(
+    con: Connection,
+    group_id: &GroupId,
+) -> Result<impl Stream<Item = GroupEvent>, Error> {
+    let mut pubsub = Connection::take(con).into_pubsub();
+    pubsub.subscribe(group_events_key(group_id)).await?;
+    Ok(pubsub.into_on_message().filter_map(|message| async move {
+        message
+            .get_payload::<RedisJson<GroupEvent>>()
+            .ok()
+            .map(|RedisJson(event)| event)
+    }))
+}
+
+/// Registers a webhook for a group. The webhook URL has to use HTTP(S).
+pub async fn create_webhook(
+    con: &mut Connection,
+    group_id: &GroupId,
+    webhook: &Webhook,
+) -> Result<(), Error> {
+    match reqwest::Url::parse(webhook.url()) {
+        Ok(url) if url.scheme() == "http" || url.scheme() == "https" => {}
+        _ => return Err(Error::InvalidWebhookUrl),
+    }
+    con.hset(webhooks_key(group_id), &webhook.id().0, RedisJson(webhook))
+        .await
+        .map_err(|err| err.into())
+}
+
+/// Lists all webhooks of a group.
+pub async fn list_webhooks(
+    con: &mut Connection,
+    group_id: &GroupId,
+) -> Result<Vec<Webhook>, Error> {
+    let webhooks: Vec<RedisJson<Webhook>> = con.hvals(webhooks_key(group_id)).await?;
+    Ok(webhooks
+        .into_iter()
+        .map(|RedisJson(webhook)| webhook)
+        .collect())
+}
+
+/// Removes a webhook and its delivery history.
+pub async fn delete_webhook(
+    con: &mut Connection,
+    group_id: &GroupId,
+    webhook_id: &WebhookId,
+) -> Result<(), Error> {
+    let (removed, ()): (usize, ()) = redis::pipe()
+        .atomic()
+        .hdel(webhooks_key(group_id), &webhook_id.0)
+        .del(webhook_deliveries_key(group_id, webhook_id))
+        .query_async(con)
+        .await?;
+    if removed == 0 {
+        return Err(Error::WebhookNotFound);
+    }
+    Ok(())
+}
+
+/// Lists the outcomes of the most recent deliveries to a webhook, newest
+/// first.
+pub async fn list_webhook_deliveries(
+    con: &mut Connection,
+    group_id: &GroupId,
+    webhook_id: &WebhookId,
+) -> Result<Vec<DeliveryStatus>, Error> {
+    let exists: bool = con.hexists(webhooks_key(group_id), &webhook_id.0).await?;
+    if !exists {
+        return Err(Error::WebhookNotFound);
+    }
+    let statuses: Vec<RedisJson<DeliveryStatus>> = con
+        .lrange(webhook_deliveries_key(group_id, webhook_id), 0, -1)
+        .await?;
+    Ok(statuses
+        .into_iter()
+        .map(|RedisJson(status)| status)
+        .collect())
+}
+
+/// Queues the event for all webhooks of the group that accept it.
+async fn enqueue_webhook_deliveries(
+    con: &mut Connection,
+    group_id: &GroupId,
+    event: &GroupEvent,
+) -> Result<(), Error> {
+    let deliveries = list_webhooks(con, group_id)
+        .await?
+        .into_iter()
+        .filter(|webhook| webhook.accepts(event))
+        .map(|webhook| RedisJson(Delivery::new(group_id.clone(), webhook, event.clone())))
+        .collect::<Vec<_>>();
+    // Redis does not accept pushing nothing.
+    if deliveries.is_empty() {
+        return Ok(());
+    }
+    con.lpush(WEBHOOK_QUEUE_KEY, deliveries)
+        .await
+        .map_err(|err| err.into())
+}
+
+/// Waits a few seconds for the next queued webhook delivery.
+///
+/// The delivery moves to the list of deliveries in progress until its outcome
+/// is recorded. Returns the delivery together with its entry in that list.
+pub async fn next_webhook_delivery(
+    con: &mut Connection,
+) -> Result<Option<(Delivery, String)>, Error> {
+    let entry: Option<String> = con
+        .brpoplpush(WEBHOOK_QUEUE_KEY, WEBHOOK_PROCESSING_KEY, 5)
+        .await?;
+    let entry = match entry {
+        Some(entry) => entry,
+        None => return Ok(None),
+    };
+    match serde_json::from_str(&entry) {
+        Ok(delivery) => Ok(Some((delivery, entry))),
+        Err(err) => {
+            // The entry can never be delivered, so it does not stay in
+            // progress.
+            con.lrem(WEBHOOK_PROCESSING_KEY, 1, &entry).await?;
+            Err(redis::RedisError::from((
+                redis::ErrorKind::TypeError,
+                "Queued delivery is not a delivery",
+                err.to_string(),
+            ))
+            .into())
+        }
+    }
+}
+
+/// Takes the lock that lets only one webhook worker run at a time, or extends
+/// it if the worker already holds it.
+///
+/// Returns whether the worker holds the lock for the given time from now on.
+/// The lock is released by letting it expire.
+///
+/// # Arguments
+///
+/// * `worker_id` unique ID of the worker.
+/// * `expiry` time after which the lock is released unless extended again.
+pub async fn lock_webhook_worker(
+    con: &mut Connection,
+    worker_id: &str,
+    expiry: std::time::Duration,
+) -> Result<bool, Error> {
+    commit!(&mut *con, pipe, {
+        redis::cmd("WATCH")
+            .arg(WEBHOOK_WORKER_KEY)
+            .query_async(con)
+            .await?;
+        let holder: Option<String> = con.get(WEBHOOK_WORKER_KEY).await?;
+        if matches!(holder, Some(holder) if holder != worker_id) {
+            redis::cmd("UNWATCH").query_async(con).await?;
+            return Ok(false);
+        }
+        pipe.cmd("SET")
+            .arg(WEBHOOK_WORKER_KEY)
+            .arg(worker_id)
+            .arg("PX")
+            .arg(expiry.as_millis() as u64)
+            .ignore();
+        Ok(true)
+    })
+}
+
+/// Moves all webhook deliveries that were in progress back to the queue.
+///
+/// Deliveries stay in progress if the worker stopped before recording their
+/// outcome. Only safe to call while holding the lock of
+/// `lock_webhook_worker`.
+///
+/// Returns the number of deliveries that were moved.
+pub async fn requeue_webhook_deliveries(con: &mut Connection) -> Result<usize, Error> {
+    let mut count = 0;
+    loop {
+        let entry: Option<String> = con
+            .rpoplpush(WEBHOOK_PROCESSING_KEY, WEBHOOK_QUEUE_KEY)
+            .await?;
+        if entry.is_none() {
+            return Ok(count);
+        }
+        count += 1;
+    }
+}
+
+/// Records the outcome of a webhook delivery and finishes it. Only the most
+/// recent outcomes are kept.
+///
+/// # Arguments
+///
+/// * `delivery` delivery that was attempted.
+/// * `entry` entry of the delivery in the list of deliveries in progress.
+/// * `status` outcome of the delivery.
+pub async fn record_webhook_delivery(
+    con: &mut Connection,
+    delivery: &Delivery,
+    entry: &str,
+    status: &DeliveryStatus,
+) -> Result<(), Error> {
+    let key = webhook_deliveries_key(delivery.group_id(), delivery.webhook().id());
+    redis::pipe()
+        .atomic()
+        .lpush(&key, RedisJson(status))
+        .ignore()
+        .ltrim(&key, 0, 49)
+        .ignore()
+        .lrem(WEBHOOK_PROCESSING_KEY, 1, entry)
+        .ignore()
+        .query_async(con)
+        .await
+        .map_err(|err| err.into())
+}
+
+/// How long an idempotency key is remembered.
+const IDEMPOTENCY_KEY_SECONDS: usize = 24 * 60 * 60;
+
+/// Game created under an idempotency key, remembered to answer retries.
+#[derive(Serialize, Deserialize, Debug)]
+struct CreatedGame {
+    /// Hash of the game that was asked for.
+    request_hash: String,
+    game_id: GameId,
+    rating_changes: Vec<RatingChange>,
+}
+
+/// Compares the games of a user with the ones of an opponent.
+///
+/// Returns the user and the opponent together with the comparison.
+pub async fn get_head_to_head(
+    con: &mut Connection,
+    group_id: &GroupId,
+    user_id: &UserId,
+    opponent_id: &UserId,
+    datetime: &chrono::DateTime<chrono::Utc>,
+) -> Result<(User, User, HeadToHead), Error> {
+    let [user, opponent]: [User; 2] =
+        read_users(con, group_id, &[user_id.clone(), opponent_id.clone()])
+            .await?
+            .try_into()
+            .expect("there is one user per ID");
+    let skill = |user: &User| {
+        user.player
+            .skill_at(datetime)
+            .unwrap_or(*user.player.skill())
+    };
+    let win_probability = true_skill().win_probability(&[skill(&user)], &[skill(&opponent)]);
+
+    let games = read_user_games(con, group_id, user_id).await?;
+    let head_to_head = statistics::head_to_head(user_id, opponent_id, &games, win_probability);
+    Ok((user, opponent, head_to_head))
+}
+
+/// Returns the probability that the winners of a game were expected to win
+/// given the skills right before the game. Returns `None` for games that did
+/// not record skills.
+fn expected_win_probability(game: &Game) -> Option<f64> {
+    let skills_before = |user_ids: &[UserId]| {
+        user_ids
+            .iter()
+            .map(|user_id| {
+                game.skill_changes
+                    .iter()
+                    .find(|skill_change| skill_change.user_id == *user_id)
+                    .map(|skill_change| skill_change.before)
+            })
+            .collect::<Option<Vec<_>>>()
+    };
+    Some(true_skill().win_probability(
+        &skills_before(&game.winner_ids)?,
+        &skills_before(&game.loser_ids)?,
+    ))
+}
+
+/// Statistics computed from the games of a group or a user.
+#[derive(Serialize, Deserialize, Debug)]
+struct CachedStatistics<T> {
+    /// Number of games the statistics were computed from. Games are never
+    /// deleted, so the statistics are up to date as long as this matches.
+    games: usize,
+    statistics: T,
+}
+
+/// Reads statistics that were computed from `games` games. Statistics that
+/// were computed from other games or cannot be read count as missing.
+async fn read_cached_statistics<T: serde::de::DeserializeOwned>(
+    con: &mut Connection,
+    key: &str,
+    games: usize,
+) -> Result<Option<T>, Error> {
+    let cached: Option<String> = con.get(key).await?;
+    Ok(cached
+        .and_then(|cached| serde_json::from_str::<CachedStatistics<T>>(&cached).ok())
+        .filter(|cached| cached.games == games)
+        .map(|cached| cached.statistics))
+}
+
+/// Stores statistics that were computed from `games` games.
+async fn write_cached_statistics<T: Serialize + Sync>(
+    con: &mut Connection,
+    key: &str,
+    games: usize,
+    statistics: &T,
+) -> Result<(), Error> {
+    con.set(key, RedisJson(CachedStatistics { games, statistics }))
+        .await
+        .map_err(|err| err.into())
+}
+
+/// Collects the statistics of all pairs of users that played together.
+///
+/// The statistics are computed once for every new game.
+pub async fn get_pair_statistics(
+    con: &mut Connection,
+    group_id: &GroupId,
+) -> Result<Vec<PairStatistics>, Error> {
+    let game_ids: Vec<GameId> = con.zrevrange(games_key(group_id), 0, -1).await?;
+    let cache_key = statistics_cache_key(group_id, "pairs");
+    if let Some(pairs) = read_cached_statistics(con, &cache_key, game_ids.len()).await? {
+        return Ok(pairs);
+    }
+
+    // Games never will be deleted, so there is no race here.
+    let games = read_games(con, group_id, &game_ids).await?;
+    let pairs = statistics::pair_statistics(&games, expected_win_probability);
+    write_cached_statistics(con, &cache_key, game_ids.len(), &pairs).await?;
+    Ok(pairs)
+}
+
+/// Collects the statistics of a user with all partners and against all
+/// opponents.
+pub async fn get_partner_statistics(
+    con: &mut Connection,
+    group_id: &GroupId,
+    user_id: &UserId,
+) -> Result<(Vec<PairStatistics>, Vec<OpponentStatistics>), Error> {
+    let games = read_user_games(con, group_id, user_id).await?;
+    let pairs = statistics::pair_statistics(&games, expected_win_probability)
+        .into_iter()
+        .filter(|pair| pair.user_ids.0 == *user_id || pair.user_ids.1 == *user_id)
+        .collect();
+    let opponents = statistics::opponent_statistics(user_id, &games, expected_win_probability);
+    Ok((pairs, opponents))
+}
+
+/// Summarizes all games of a user.
+///
+/// Games within the 30 days before `datetime` count as recent games. All
+/// other statistics are computed once for every new game of the user.
+pub async fn get_user_statistics(
+    con: &mut Connection,
+    group_id: &GroupId,
+    user_id: &UserId,
+    datetime: &chrono::DateTime<chrono::Utc>,
+) -> Result<UserStatistics, Error> {
+    let since = *datetime - chrono::Duration::days(30);
+    let user_games_key = user_games_key(group_id, user_id);
+    let game_count: usize = con.zcard(&user_games_key).await?;
+    let cache_key = statistics_cache_key(group_id, &("user:".to_owned() + &user_id.0));
+    let mut statistics = match read_cached_statistics(con, &cache_key, game_count).await? {
+        Some(statistics) => statistics,
+        None => {
+            let games = read_user_games(con, group_id, user_id).await?;
+            let statistics = statistics::user_statistics(
+                user_id,
+                &games,
+                &since,
+                conservative_score,
+                expected_win_probability,
+            );
+            write_cached_statistics(con, &cache_key, games.len(), &statistics).await?;
+            statistics
+        }
+    };
+
+    // Games of a user are scored by their timestamp.
+    statistics.recent_games = con
+        .zcount(
+            &user_games_key,
+            format!("({}", since.naive_utc().timestamp_millis()),
+            "+inf",
+        )
+        .await?;
+    Ok(statistics)
+}
+
+/// Summary of all games of a group.
+#[derive(Clone, Debug)]
+pub struct GroupStatistics {
+    games: usize,
+    side_advantages: Vec<SideAdvantage>,
+}
+
+impl GroupStatistics {
+    pub fn games(&self) -> usize {
+        self.games
+    }
+
+    /// Learned advantages of the home side of all tables, ordered by table.
+    pub fn side_advantages(&self) -> &[SideAdvantage] {
+        &self.side_advantages
+    }
+}
+
+/// Summarizes all games of a group.
+pub async fn get_group_statistics(
+    con: &mut Connection,
+    group_id: &GroupId,
+) -> Result<GroupStatistics, Error> {
+    let games = con.zcard(games_key(group_id)).await?;
+    let side_advantages: Vec<RedisJson<SideAdvantage>> =
+        con.hvals(side_advantages_key(group_id)).await?;
+    let mut side_advantages = side_advantages
+        .into_iter()
+        .map(|RedisJson(side_advantage)| side_advantage)
+        .collect::<Vec<_>>();
+    side_advantages.sort_by(|a, b| a.table.cmp(&b.table));
+    Ok(GroupStatistics {
+        games,
+        side_advantages,
+    })
+}
+
+/// Rating of a user as it is shown on the leaderboard.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct Rating {
+    mu: f64,
+    sigma: f64,
+    /// Conservative estimate of the skill that orders the leaderboard.
+    score: f64,
+    /// Position on the leaderboard starting at 1.
+    rank: usize,
+}
+
+/// Rating of a user before and after one or more games.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RatingChange {
+    user_id: UserId,
+    before: Rating,
+    after: Rating,
+}
+
+/// Counts the users in the leaderboard index with a score above `score`.
+async fn count_above(con: &mut Connection, key: &str, score: f64) -> Result<usize, Error> {
+    con.zcount(key, format!("({}", score), "+inf")
+        .await
+        .map_err(|err| err.into())
+}
+
+/// Turns the skill changes of one or more games into rating changes.
+///
+/// Mean, standard deviation and score are the ones at the time of the games.
+/// Ranks place the players in the leaderboard index, once with their skills
+/// before and once with their skills after the games. The index orders users
+/// by the score of their latest skill estimate.
+///
+/// Has to run within the transaction that adds the games, so that the index
+/// does not contain them yet.
+async fn rank_skill_changes(
+    con: &mut Connection,
+    group_id: &GroupId,
+    skill_changes: &[SkillChange],
+) -> Result<Vec<RatingChange>, Error> {
+    let leaderboard_index_key = leaderboard_index_key(group_id);
+    redis::cmd("WATCH")
+        .arg(&leaderboard_index_key)
+        .query_async(con)
+        .await?;
+
+    // Users that were never indexed count with their score before the games.
+    let mut before_scores = Vec::new();
+    for skill_change in skill_changes {
+        let score: Option<f64> = con
+            .zscore(&leaderboard_index_key, &skill_change.user_id.0)
+            .await?;
+        before_scores.push(score);
+    }
+    let after_scores = skill_changes
+        .iter()
+        .map(|skill_change| conservative_score(&skill_change.after))
+        .collect::<Vec<_>>();
+
+    let rating = |skill: &Message, rank: usize| {
+        let (mu, sigma2) = skill.to_mu_sigma2();
+        Rating {
+            mu,
+            sigma: sigma2.sqrt(),
+            score: conservative_score(skill),
+            rank,
+        }
+    };
+
+    let mut rating_changes = Vec::new();
+    for (index, skill_change) in skill_changes.iter().enumerate() {
+        let before_score =
+            before_scores[index].unwrap_or_else(|| conservative_score(&skill_change.before));
+        let before_rank = 1 + count_above(con, &leaderboard_index_key, before_score).await?;
+
+        // The index still has the players with their scores from before the
+        // games.
+        let after_score = after_scores[index];
+        let indexed_above = count_above(con, &leaderboard_index_key, after_score).await?;
+        let players_before_above = before_scores
+            .iter()
+            .flatten()
+            .filter(|score| **score > after_score)
+            .count();
+        let players_after_above = after_scores
+            .iter()
+            .filter(|score| **score > after_score)
+            .count();
+        let after_rank = 1 + indexed_above + players_after_above - players_before_above;
+
+        rating_changes.push(RatingChange {
+            user_id: skill_change.user_id.clone(),
+            before: rating(&skill_change.before, before_rank),
+            after: rating(&skill_change.after, after_rank),
+        });
+    }
+    Ok(rating_changes)
+}
+
+/// Makes the estimator that rates all games.
+fn true_skill() -> TrueSkill {
+    TrueSkill::new(Player::default_sigma() / 2.0, 0.0)
+}
+
+/// Maps a skill belief to a conservative estimate of the skill.
+fn conservative_score(skill: &Message) -> f64 {
+    true_skill().score(skill)
+}
+
+/// Score of a player in the leaderboard index, which is the score of the
+/// latest skill estimate.
+fn index_score(player: &Player) -> f64 {
+    conservative_score(player.skill())
+}
+
+/// Maps a player to a conservative estimate of the skill at a point in time.
+/// Players with a later skill estimate count with that one.
+fn map_score(player: &Player, datetime: &chrono::DateTime<chrono::Utc>) -> f64 {
+    conservative_score(&player.skill_at(datetime).unwrap_or(*player.skill()))
+}
+
+/// Orders scores from the highest to the lowest. Scores that are not a number
+/// go last.
+fn by_descending_score(score_a: f64, score_b: f64) -> std::cmp::Ordering {
+    match (score_a.is_nan(), score_b.is_nan()) {
+        (false, false) => score_b
+            .partial_cmp(&score_a)
+            .unwrap_or(std::cmp::Ordering::Equal),
+        (nan_a, nan_b) => nan_a.cmp(&nan_b),
+    }
+}
+
+fn group_key_prefix(group_id: &GroupId) -> String {
+    "group:".to_owned() + &group_id.0
+}
+
+fn group_settings_key(group_id: &GroupId) -> String {
+    group_key_prefix(group_id) + ":settings"
+}
+
+fn user_id_key(group_id: &GroupId) -> String {
+    group_key_prefix(group_id) + ":user.id"
+}
+
+fn user_name_index_key(group_id: &GroupId) -> String {
+    group_key_prefix(group_id) + ":user.name.index"
+}
+
+fn user_name_token_index_key(group_id: &GroupId) -> String {
+    group_key_prefix(group_id) + ":user.name.token.index"
+}
+
+fn leaderboard_index_key(group_id: &GroupId) -> String {
+    group_key_prefix(group_id) + ":leaderboard.index"
+}
+
+fn user_activity_key(group_id: &GroupId) -> String {
+    group_key_prefix(group_id) + ":user.activity"
+}
 
 fn user_key(group_id: &GroupId, user_id: &UserId) -> String {
     group_key_prefix(group_id) + ":user:" + &user_id.0
@@ -655,3 +3110,347 @@ fn game_key(group_id: &GroupId, game_id: &GameId) -> String {
 fn games_key(group_id: &GroupId) -> String {
     group_key_prefix(group_id) + ":games"
 }
+
+/// Queue of webhook deliveries of all groups.
+const WEBHOOK_QUEUE_KEY: &str = "webhook.deliveries";
+
+/// Webhook deliveries of all groups that are being attempted.
+const WEBHOOK_PROCESSING_KEY: &str = "webhook.deliveries.processing";
+
+/// Lock of the webhook worker that is allowed to attempt deliveries.
+const WEBHOOK_WORKER_KEY: &str = "webhook.worker";
+
+fn webhooks_key(group_id: &GroupId) -> String {
+    group_key_prefix(group_id) + ":webhooks"
+}
+
+fn webhook_deliveries_key(group_id: &GroupId, webhook_id: &WebhookId) -> String {
+    group_key_prefix(group_id) + ":webhook.deliveries:" + &webhook_id.0
+}
+
+fn group_events_key(group_id: &GroupId) -> String {
+    group_key_prefix(group_id) + ":events"
+}
+
+fn seasons_key(group_id: &GroupId) -> String {
+    group_key_prefix(group_id) + ":seasons"
+}
+
+fn season_standings_key(group_id: &GroupId, number: u32) -> String {
+    group_key_prefix(group_id) + ":season.standings:" + &number.to_string()
+}
+
+fn tournaments_key(group_id: &GroupId) -> String {
+    group_key_prefix(group_id) + ":tournaments"
+}
+
+fn leagues_key(group_id: &GroupId) -> String {
+    group_key_prefix(group_id) + ":leagues"
+}
+
+fn user_leagues_key(group_id: &GroupId, user_id: &UserId) -> String {
+    group_key_prefix(group_id) + ":user.leagues:" + &user_id.0
+}
+
+fn queue_key(group_id: &GroupId) -> String {
+    group_key_prefix(group_id) + ":queue"
+}
+
+fn side_advantages_key(group_id: &GroupId) -> String {
+    group_key_prefix(group_id) + ":side_advantages"
+}
+
+fn statistics_cache_key(group_id: &GroupId, name: &str) -> String {
+    group_key_prefix(group_id) + ":statistics.cache:" + name
+}
+
+fn smoothed_ratings_key(group_id: &GroupId) -> String {
+    group_key_prefix(group_id) + ":smoothed_ratings"
+}
+
+fn game_idempotency_key(group_id: &GroupId, idempotency_key: &str) -> String {
+    group_key_prefix(group_id) + ":game.idempotency:" + idempotency_key
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user_ids(ids: &[&str]) -> Vec<UserId> {
+        ids.iter().map(|id| UserId(id.to_string())).collect()
+    }
+
+    fn game(winner_ids: &[&str], loser_ids: &[&str]) -> Game {
+        Game::new(
+            GameId(uuid::Uuid::new_v4().simple().to_string()),
+            chrono::Utc::now(),
+            user_ids(winner_ids),
+            user_ids(loser_ids),
+        )
+    }
+
+    fn positions(entries: &[(&str, Position)]) -> HashMap<UserId, Position> {
+        entries
+            .iter()
+            .map(|(id, position)| (UserId(id.to_string()), *position))
+            .collect()
+    }
+
+    #[test]
+    fn test_validate_team_pairings() {
+        use crate::group_settings::{GameMode, GameRules};
+
+        let entrants = vec![
+            user_ids(&["a", "b"]),
+            user_ids(&["c", "d"]),
+            user_ids(&["e"]),
+        ];
+        assert_eq!(
+            validate_team_pairings(&GroupSettings::default(), &entrants),
+            Ok(())
+        );
+
+        let equal_sizes = GroupSettings {
+            game_rules: GameRules {
+                equal_team_sizes: true,
+                ..GameRules::default()
+            },
+            ..GroupSettings::default()
+        };
+        assert_eq!(
+            validate_team_pairings(&equal_sizes, &entrants),
+            Err(GameError::UnequalTeamSizes {
+                winners: 2,
+                losers: 1
+            })
+        );
+
+        // Games that fit no game mode are only rated overall.
+        let doubles = GroupSettings {
+            game_modes: vec![GameMode {
+                name: "doubles".to_string(),
+                team_size: 2,
+            }],
+            ..GroupSettings::default()
+        };
+        assert_eq!(validate_team_pairings(&doubles, &entrants), Ok(()));
+
+        let pairs = GroupSettings {
+            game_rules: GameRules {
+                allowed_team_sizes: vec![2],
+                ..GameRules::default()
+            },
+            ..GroupSettings::default()
+        };
+        assert_eq!(validate_team_pairings(&pairs, &entrants[..2]), Ok(()));
+        assert_eq!(
+            validate_team_pairings(&pairs, &entrants),
+            Err(GameError::TeamSizeNotAllowed {
+                size: 1,
+                allowed: vec![2]
+            })
+        );
+    }
+
+    #[test]
+    fn test_validate_weights() {
+        let weights = |entries: &[(&str, f64)]| {
+            entries
+                .iter()
+                .map(|(id, weight)| (UserId(id.to_string()), *weight))
+                .collect::<HashMap<_, _>>()
+        };
+
+        assert_eq!(game(&["a"], &["b"]).validate_weights(), Ok(()));
+        assert_eq!(
+            game(&["a", "b"], &["c"])
+                .with_weights(weights(&[("a", 0.5), ("c", 1.0)]))
+                .validate_weights(),
+            Ok(())
+        );
+        for weight in [0.0, -0.5, 1.5, f64::NAN] {
+            assert_eq!(
+                game(&["a"], &["b"])
+                    .with_weights(weights(&[("b", weight)]))
+                    .validate_weights(),
+                Err(GameError::InvalidWeight {
+                    user_id: UserId("b".to_string())
+                })
+            );
+        }
+        assert_eq!(
+            game(&["a"], &["b"])
+                .with_weights(weights(&[("a", 0.5), ("c", 0.5)]))
+                .validate_weights(),
+            Err(GameError::WeightOfNonPlayer {
+                user_id: UserId("c".to_string())
+            })
+        );
+    }
+
+    #[test]
+    fn test_rate_players_with_sides() {
+        let datetime = chrono::Utc::now();
+        let user_ids = user_ids(&["a", "b"]);
+        let players = vec![Player::new(datetime), Player::new(datetime)];
+        let advantage = SideAdvantage::new(None).advantage;
+        let rate = |side: Option<Side>| {
+            rate_players(
+                &user_ids,
+                &players,
+                &[1.0, 1.0],
+                1,
+                side.map(|side| (side, advantage)),
+                &datetime,
+            )
+            .unwrap()
+        };
+
+        let (_, advantage_after) = rate(None);
+        assert!(advantage_after.is_none());
+        // Wins of the home side make the advantage larger and more certain,
+        // wins of the away side make it smaller.
+        let (_, advantage_after) = rate(Some(Side::Home));
+        let (mu, sigma2) = advantage_after.unwrap().to_mu_sigma2();
+        assert!(mu > 0.0 && sigma2 < advantage.to_mu_sigma2().1);
+        let (skills, advantage_after) = rate(Some(Side::Away));
+        assert!(advantage_after.unwrap().to_mu_sigma2().0 < 0.0);
+        // The winner still gains.
+        assert!(skills[0].1.to_mu_sigma2().0 > skills[0].0.to_mu_sigma2().0);
+    }
+
+    #[test]
+    fn test_validate_positions() {
+        use Position::{Defense, Offense};
+
+        assert_eq!(game(&["a", "b"], &["c", "d"]).validate_positions(), Ok(()));
+        let valid = positions(&[
+            ("a", Offense),
+            ("b", Defense),
+            ("c", Defense),
+            ("d", Offense),
+        ]);
+        assert_eq!(
+            game(&["a", "b"], &["c", "d"])
+                .with_positions(valid)
+                .validate_positions(),
+            Ok(())
+        );
+
+        let double_offense = positions(&[
+            ("a", Offense),
+            ("b", Offense),
+            ("c", Defense),
+            ("d", Offense),
+        ]);
+        assert_eq!(
+            game(&["a", "b"], &["c", "d"])
+                .with_positions(double_offense)
+                .validate_positions(),
+            Err(GameError::InvalidPositions {
+                team: Team::Winners
+            })
+        );
+        let missing = positions(&[("a", Offense), ("b", Defense), ("c", Defense)]);
+        assert_eq!(
+            game(&["a", "b"], &["c", "d"])
+                .with_positions(missing)
+                .validate_positions(),
+            Err(GameError::MissingPosition {
+                user_id: UserId("d".to_string())
+            })
+        );
+        let non_player = positions(&[
+            ("a", Offense),
+            ("b", Defense),
+            ("c", Defense),
+            ("d", Offense),
+            ("e", Offense),
+        ]);
+        assert_eq!(
+            game(&["a", "b"], &["c", "d"])
+                .with_positions(non_player)
+                .validate_positions(),
+            Err(GameError::PositionOfNonPlayer {
+                user_id: UserId("e".to_string())
+            })
+        );
+        assert_eq!(
+            game(&["a"], &["b"])
+                .with_positions(positions(&[("a", Offense), ("b", Defense)]))
+                .validate_positions(),
+            Err(GameError::PositionsNotAllowed {
+                team: Team::Winners,
+                size: 1
+            })
+        );
+        let three = positions(&[
+            ("a", Offense),
+            ("b", Defense),
+            ("c", Defense),
+            ("d", Offense),
+            ("e", Offense),
+        ]);
+        assert_eq!(
+            game(&["a", "b"], &["c", "d", "e"])
+                .with_positions(three)
+                .validate_positions(),
+            Err(GameError::PositionsNotAllowed {
+                team: Team::Losers,
+                size: 3
+            })
+        );
+    }
+
+    #[test]
+    fn test_game_page_ranks() {
+        assert_eq!(game_page_ranks(0, None), Some((0, 99)));
+        assert_eq!(game_page_ranks(5, None), Some((5, 104)));
+        // A season with 3 games and 10 later games.
+        assert_eq!(game_page_ranks(0, Some((10, 3))), Some((10, 12)));
+        assert_eq!(game_page_ranks(11, Some((10, 3))), Some((11, 12)));
+        assert_eq!(game_page_ranks(13, Some((10, 3))), None);
+        assert_eq!(game_page_ranks(0, Some((10, 0))), None);
+        // Long seasons span several pages.
+        assert_eq!(game_page_ranks(0, Some((0, 500))), Some((0, 99)));
+        assert_eq!(game_page_ranks(450, Some((0, 500))), Some((450, 499)));
+    }
+
+    #[test]
+    fn test_parse_token_entry() {
+        assert_eq!(
+            parse_token_entry("doe:jane doe:42"),
+            Some((UserId("42".to_string()), "jane doe".to_string()))
+        );
+        // Names may contain colons, tokens and user IDs do not.
+        assert_eq!(
+            parse_token_entry("b:a:b:42"),
+            Some((UserId("42".to_string()), "a:b".to_string()))
+        );
+        assert_eq!(parse_token_entry("doe:42"), None);
+    }
+
+    #[test]
+    fn test_request_hash() {
+        let weights = |entries: &[(&str, f64)]| {
+            entries
+                .iter()
+                .map(|(id, weight)| (UserId(id.to_string()), *weight))
+                .collect::<HashMap<_, _>>()
+        };
+        let retry_a =
+            game(&["a", "b"], &["c", "d"]).with_weights(weights(&[("a", 0.5), ("c", 0.5)]));
+        let retry_b =
+            game(&["a", "b"], &["c", "d"]).with_weights(weights(&[("c", 0.5), ("a", 0.5)]));
+        // Retries get new IDs and points in time, but ask for the same game.
+        assert_eq!(request_hash(&retry_a), request_hash(&retry_b));
+
+        let swapped =
+            game(&["c", "d"], &["a", "b"]).with_weights(weights(&[("a", 0.5), ("c", 0.5)]));
+        assert_ne!(request_hash(&retry_a), request_hash(&swapped));
+        let sided = game(&["a", "b"], &["c", "d"])
+            .with_weights(weights(&[("a", 0.5), ("c", 0.5)]))
+            .with_side(None, Side::Home);
+        assert_ne!(request_hash(&retry_a), request_hash(&sided));
+    }
+}
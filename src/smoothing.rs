@@ -0,0 +1,214 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::message::Message;
+use crate::player::Player;
+use crate::skill_base::{Game, UserId};
+use crate::true_skill::TrueSkill;
+
+/// Message that carries no information.
+const UNIFORM: Message = Message { pi: 0.0, tau: 0.0 };
+
+/// Maximal number of forward and backward sweeps over the game history.
+const MAX_ITERATIONS: usize = 20;
+
+/// Largest change of a game message at which smoothing stops.
+const CONVERGENCE_THRESHOLD: f64 = 1e-6;
+
+/// Skill of a user at the time of one of their games.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SmoothedSkill {
+    pub datetime: chrono::DateTime<chrono::Utc>,
+    pub skill: Message,
+}
+
+/// Game of a user in the chain of their skills over time.
+struct Appearance {
+    /// Index of the game.
+    game: usize,
+    /// Index of the user among the players of the game, winners first.
+    slot: usize,
+}
+
+/// Widens a message by the change of skill between two points in time.
+fn drift(
+    message: &Message,
+    from: chrono::DateTime<chrono::Utc>,
+    to: &chrono::DateTime<chrono::Utc>,
+) -> Message {
+    if message.pi == 0.0 {
+        return *message;
+    }
+    let mut player = Player::new(from);
+    player.set_skill(*message, from);
+    player.skill_at(to).unwrap_or(*message)
+}
+
+/// Estimates the skill of every user at each of their games from the whole
+/// game history, in the spirit of TrueSkill Through Time.
+///
+/// Each user has a chain of skills, one per game, that drift apart over time
+/// like the skill of a `Player`. The first forward sweep over the games
+/// matches the regular online ratings. Later sweeps also pass the evidence
+/// of later games back in time until the game messages converge, so that
+/// early games are judged with what is known about the players today.
+///
+/// # Arguments
+///
+/// * `games` all games from the oldest to the latest.
+/// * `true_skill` TrueSkill estimator that rates every single game.
+pub fn smooth(games: &[Game], true_skill: &TrueSkill) -> HashMap<UserId, Vec<SmoothedSkill>> {
+    let players = games
+        .iter()
+        .map(|game| {
+            game.winner_ids()
+                .iter()
+                .chain(game.loser_ids())
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    let mut chains: HashMap<&UserId, Vec<Appearance>> = HashMap::new();
+    for (game, user_ids) in players.iter().enumerate() {
+        for (slot, user_id) in user_ids.iter().enumerate() {
+            chains
+                .entry(user_id)
+                .or_default()
+                .push(Appearance { game, slot });
+        }
+    }
+
+    // Messages from every game to the skills of its players, from the
+    // previous skill of a player forward in time and from the next skill of
+    // a player backward in time.
+    let mut from_games = players
+        .iter()
+        .map(|user_ids| vec![UNIFORM; user_ids.len()])
+        .collect::<Vec<_>>();
+    let mut forward = from_games.clone();
+    let mut backward = from_games.clone();
+    let prior = Message::from_mu_sigma2(Player::default_mean(), Player::default_sigma().powi(2));
+
+    for _ in 0..MAX_ITERATIONS {
+        let mut change = 0.0_f64;
+        let mut last_skills: HashMap<&UserId, (Message, chrono::DateTime<chrono::Utc>)> =
+            HashMap::new();
+        for (index, game) in games.iter().enumerate() {
+            let datetime = *game.datetime();
+            for (slot, user_id) in players[index].iter().enumerate() {
+                forward[index][slot] = match last_skills.get(user_id) {
+                    Some((skill, last_datetime)) => drift(skill, *last_datetime, &datetime),
+                    None => prior,
+                };
+            }
+
+            let teams = players[index]
+                .iter()
+                .enumerate()
+                .map(|(slot, user_id)| {
+                    (
+                        game.weight(user_id),
+                        forward[index][slot].include(&backward[index][slot]),
+                    )
+                })
+                .collect::<Vec<_>>();
+            let (winners, losers) = teams.split_at(game.winner_ids().len());
+            let updates = true_skill.rank_pass(&[winners.to_vec(), losers.to_vec()], &[0, 1]);
+
+            for (slot, update) in updates.into_iter().flatten().enumerate() {
+                let old = from_games[index][slot];
+                change = change
+                    .max((update.pi - old.pi).abs())
+                    .max((update.tau - old.tau).abs());
+                from_games[index][slot] = update;
+                last_skills.insert(
+                    players[index][slot],
+                    (forward[index][slot].include(&update), datetime),
+                );
+            }
+        }
+
+        for appearances in chains.values() {
+            let mut later = UNIFORM;
+            let mut later_datetime = None;
+            for appearance in appearances.iter().rev() {
+                let datetime = *games[appearance.game].datetime();
+                backward[appearance.game][appearance.slot] = match later_datetime {
+                    Some(later_datetime) => drift(&later, datetime, &later_datetime),
+                    None => UNIFORM,
+                };
+                later = backward[appearance.game][appearance.slot]
+                    .include(&from_games[appearance.game][appearance.slot]);
+                later_datetime = Some(datetime);
+            }
+        }
+
+        if change < CONVERGENCE_THRESHOLD {
+            break;
+        }
+    }
+
+    chains
+        .into_iter()
+        .map(|(user_id, appearances)| {
+            let skills = appearances
+                .iter()
+                .map(|Appearance { game, slot }| SmoothedSkill {
+                    datetime: *games[*game].datetime(),
+                    skill: forward[*game][*slot]
+                        .include(&from_games[*game][*slot])
+                        .include(&backward[*game][*slot]),
+                })
+                .collect();
+            (user_id.clone(), skills)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::skill_base::GameId;
+
+    fn true_skill() -> TrueSkill {
+        TrueSkill::new(Player::default_sigma() / 2.0, 0.0)
+    }
+
+    fn game(days: i64, winner: &str, loser: &str) -> Game {
+        Game::new(
+            GameId::from(format!("{}{}{}", days, winner, loser)),
+            chrono::Utc::now() - chrono::Duration::days(100 - days),
+            vec![UserId::from(winner.to_owned())],
+            vec![UserId::from(loser.to_owned())],
+        )
+    }
+
+    fn mu(skill: &SmoothedSkill) -> f64 {
+        skill.skill.to_mu_sigma2().0
+    }
+
+    #[test]
+    fn test_single_game() {
+        let skills = smooth(&[game(0, "a", "b")], &true_skill());
+        let a = &skills[&UserId::from("a".to_owned())];
+        let b = &skills[&UserId::from("b".to_owned())];
+        assert_eq!(a.len(), 1);
+        assert!((mu(&a[0]) - 29.205).abs() < 1e-3);
+        assert!((mu(&b[0]) - 20.795).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_later_games_inform_earlier_skills() {
+        // "a" beats "b" once and then keeps losing against "c".
+        let mut games = vec![game(0, "a", "b")];
+        games.extend((1..=5).map(|days| game(days, "c", "a")));
+        let skills = smooth(&games, &true_skill());
+        let a = &skills[&UserId::from("a".to_owned())];
+        assert_eq!(a.len(), 6);
+        // Without smoothing "a" would be at 29.205 after the first game.
+        assert!(mu(&a[0]) < 29.0);
+        // Skills of the same user hardly differ between close games.
+        assert!((mu(&a[4]) - mu(&a[5])).abs() < 1.0);
+    }
+}
@@ -0,0 +1,66 @@
+use std::convert::TryInto;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::skill_base;
+
+/// Exported games and users of a group.
+#[derive(Deserialize, Debug)]
+pub struct Snapshot {
+    pub games: Vec<Game>,
+    #[serde(default)]
+    pub users: Vec<User>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct User {
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Game {
+    pub id: String,
+    pub winner_ids: Vec<String>,
+    pub loser_ids: Vec<String>,
+    /// Milliseconds since the Unix epoch.
+    pub timestamp: u128,
+}
+
+impl Snapshot {
+    /// Reads a snapshot from a JSON file.
+    pub fn read(path: impl AsRef<Path>) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut file = File::open(path)?;
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)?;
+        Ok(serde_json::from_str(&contents)?)
+    }
+}
+
+impl Game {
+    /// Turns the exported game into a game that can be rated.
+    pub fn into_game(self) -> skill_base::Game {
+        let datetime = chrono::DateTime::<chrono::Utc>::from_utc(
+            chrono::NaiveDateTime::from_timestamp(
+                (self.timestamp / 1000).try_into().unwrap(),
+                (self.timestamp % 1000 * 1_000_000).try_into().unwrap(),
+            ),
+            chrono::Utc,
+        );
+        skill_base::Game::new(
+            self.id.into(),
+            datetime,
+            self.winner_ids
+                .into_iter()
+                .map(skill_base::UserId::from)
+                .collect(),
+            self.loser_ids
+                .into_iter()
+                .map(skill_base::UserId::from)
+                .collect(),
+        )
+    }
+}
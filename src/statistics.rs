@@ -0,0 +1,439 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::message::Message;
+use crate::skill_base::{Game, UserId};
+
+/// Number of most recent meetings that are part of a head-to-head comparison.
+const LAST_MEETINGS: usize = 5;
+/// Number of opponents that are part of the statistics of a user.
+const FREQUENT_OPPONENTS: usize = 3;
+
+/// Wins and losses of a user.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Record {
+    pub wins: usize,
+    pub losses: usize,
+}
+
+impl Record {
+    fn add(&mut self, won: bool) {
+        if won {
+            self.wins += 1;
+        } else {
+            self.losses += 1;
+        }
+    }
+
+    pub fn games(&self) -> usize {
+        self.wins + self.losses
+    }
+}
+
+/// Compares the games of two users.
+#[derive(Clone, Debug)]
+pub struct HeadToHead {
+    /// Record of the user when playing against the opponent.
+    pub against: Record,
+    /// Record of the user when playing together with the opponent.
+    pub together: Record,
+    /// Most recent games the two played against each other, most recent
+    /// first.
+    pub last_meetings: Vec<Game>,
+    /// Probability that the user wins a game against the opponent.
+    pub win_probability: f64,
+}
+
+/// Tells whether a user won the game. Returns `None` if the user did not play.
+pub fn has_won(game: &Game, user_id: &UserId) -> Option<bool> {
+    if game.winner_ids().contains(user_id) {
+        Some(true)
+    } else if game.loser_ids().contains(user_id) {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+/// Compares the games of a user with the ones of an opponent.
+///
+/// # Arguments
+///
+/// * `user_id` ID of the user.
+/// * `opponent_id` ID of the opponent.
+/// * `games` games of the user, most recent first.
+/// * `win_probability` probability that the user wins against the opponent.
+pub fn head_to_head(
+    user_id: &UserId,
+    opponent_id: &UserId,
+    games: &[Game],
+    win_probability: f64,
+) -> HeadToHead {
+    let mut head_to_head = HeadToHead {
+        against: Record::default(),
+        together: Record::default(),
+        last_meetings: Vec::new(),
+        win_probability,
+    };
+    for game in games {
+        let (user_won, opponent_won) = match (has_won(game, user_id), has_won(game, opponent_id)) {
+            (Some(user_won), Some(opponent_won)) => (user_won, opponent_won),
+            _ => continue,
+        };
+        if user_won == opponent_won {
+            head_to_head.together.add(user_won);
+        } else {
+            head_to_head.against.add(user_won);
+            if head_to_head.last_meetings.len() < LAST_MEETINGS {
+                head_to_head.last_meetings.push(game.clone());
+            }
+        }
+    }
+    head_to_head
+}
+
+/// Games of two users playing on the same team.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PairStatistics {
+    pub user_ids: (UserId, UserId),
+    pub record: Record,
+    /// Wins beyond the ones that were expected from the skills of the teams.
+    /// Only covers games with known skills.
+    pub performance: f64,
+}
+
+impl PairStatistics {
+    pub fn win_rate(&self) -> f64 {
+        self.record.wins as f64 / self.record.games() as f64
+    }
+}
+
+/// Games of a user against an opponent.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct OpponentStatistics {
+    pub opponent_id: UserId,
+    pub record: Record,
+    /// Wins beyond the ones that were expected from the skills of the teams.
+    /// Only covers games with known skills.
+    pub performance: f64,
+}
+
+/// Returns the wins beyond expectation for one team of a game.
+fn performance(won: bool, win_probability: Option<f64>) -> f64 {
+    match win_probability {
+        Some(win_probability) if won => 1.0 - win_probability,
+        Some(win_probability) => -win_probability,
+        None => 0.0,
+    }
+}
+
+/// Collects the statistics of all pairs of users that played on the same
+/// team.
+///
+/// # Arguments
+///
+/// * `games` games to consider.
+/// * `win_probability` probability that the winners of a game were expected
+///   to win, if known.
+pub fn pair_statistics<F>(games: &[Game], win_probability: F) -> Vec<PairStatistics>
+where
+    F: Fn(&Game) -> Option<f64>,
+{
+    let mut pairs: HashMap<(UserId, UserId), PairStatistics> = HashMap::new();
+    for game in games {
+        let winner_probability = win_probability(game);
+        for (team, won) in [(game.winner_ids(), true), (game.loser_ids(), false)] {
+            let team_probability = winner_probability.map(|p| if won { p } else { 1.0 - p });
+            for (index, first_id) in team.iter().enumerate() {
+                for second_id in &team[index + 1..] {
+                    let user_ids = if first_id < second_id {
+                        (first_id.clone(), second_id.clone())
+                    } else {
+                        (second_id.clone(), first_id.clone())
+                    };
+                    let pair = pairs
+                        .entry(user_ids.clone())
+                        .or_insert_with(|| PairStatistics {
+                            user_ids,
+                            record: Record::default(),
+                            performance: 0.0,
+                        });
+                    pair.record.add(won);
+                    pair.performance += performance(won, team_probability);
+                }
+            }
+        }
+    }
+    pairs.into_values().collect()
+}
+
+/// Collects the statistics of a user against every opponent.
+///
+/// # Arguments
+///
+/// * `user_id` ID of the user.
+/// * `games` games of the user.
+/// * `win_probability` probability that the winners of a game were expected
+///   to win, if known.
+pub fn opponent_statistics<F>(
+    user_id: &UserId,
+    games: &[Game],
+    win_probability: F,
+) -> Vec<OpponentStatistics>
+where
+    F: Fn(&Game) -> Option<f64>,
+{
+    let mut opponents: HashMap<UserId, OpponentStatistics> = HashMap::new();
+    for game in games {
+        let (won, opponent_ids) = match has_won(game, user_id) {
+            Some(true) => (true, game.loser_ids()),
+            Some(false) => (false, game.winner_ids()),
+            None => continue,
+        };
+        let team_probability = win_probability(game).map(|p| if won { p } else { 1.0 - p });
+        for opponent_id in opponent_ids {
+            let opponent =
+                opponents
+                    .entry(opponent_id.clone())
+                    .or_insert_with(|| OpponentStatistics {
+                        opponent_id: opponent_id.clone(),
+                        record: Record::default(),
+                        performance: 0.0,
+                    });
+            opponent.record.add(won);
+            opponent.performance += performance(won, team_probability);
+        }
+    }
+    opponents.into_values().collect()
+}
+
+/// Highest rating a user ever had.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct Peak {
+    pub score: f64,
+    pub datetime: chrono::DateTime<chrono::Utc>,
+}
+
+/// Summarizes all games of a user.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct UserStatistics {
+    pub record: Record,
+    /// Number of games the user won since the last loss.
+    pub current_win_streak: usize,
+    pub longest_win_streak: usize,
+    /// Only covers games with known skills.
+    pub peak: Option<Peak>,
+    /// Number of games since the queried point in time.
+    pub recent_games: usize,
+    /// Opponents the user played against most often.
+    pub frequent_opponents: Vec<OpponentStatistics>,
+}
+
+/// Summarizes all games of a user.
+///
+/// # Arguments
+///
+/// * `user_id` ID of the user.
+/// * `games` games of the user, most recent first.
+/// * `since` games after this point in time count as recent.
+/// * `score` maps a skill to the rating of a user.
+/// * `win_probability` probability that the winners of a game were expected
+///   to win, if known.
+pub fn user_statistics<F, W>(
+    user_id: &UserId,
+    games: &[Game],
+    since: &chrono::DateTime<chrono::Utc>,
+    score: F,
+    win_probability: W,
+) -> UserStatistics
+where
+    F: Fn(&Message) -> f64,
+    W: Fn(&Game) -> Option<f64>,
+{
+    let mut record = Record::default();
+    let mut current_win_streak = None;
+    let mut longest_win_streak = 0;
+    let mut win_streak = 0;
+    let mut peak: Option<Peak> = None;
+    let mut recent_games = 0;
+
+    for game in games {
+        let won = match has_won(game, user_id) {
+            Some(won) => won,
+            None => continue,
+        };
+        record.add(won);
+        if won {
+            win_streak += 1;
+        } else {
+            current_win_streak.get_or_insert(win_streak);
+            win_streak = 0;
+        }
+        longest_win_streak = longest_win_streak.max(win_streak);
+
+        if game.datetime() > since {
+            recent_games += 1;
+        }
+
+        let skill_after = game
+            .skill_changes()
+            .iter()
+            .find(|skill_change| skill_change.user_id() == user_id)
+            .map(|skill_change| score(skill_change.after()));
+        if let Some(game_score) = skill_after {
+            if peak.is_none_or(|peak| game_score > peak.score) {
+                peak = Some(Peak {
+                    score: game_score,
+                    datetime: *game.datetime(),
+                });
+            }
+        }
+    }
+
+    let mut frequent_opponents = opponent_statistics(user_id, games, win_probability);
+    frequent_opponents.sort_by(|opponent_a, opponent_b| {
+        opponent_b
+            .record
+            .games()
+            .cmp(&opponent_a.record.games())
+            .then_with(|| opponent_a.opponent_id.cmp(&opponent_b.opponent_id))
+    });
+    frequent_opponents.truncate(FREQUENT_OPPONENTS);
+
+    UserStatistics {
+        record,
+        current_win_streak: current_win_streak.unwrap_or(win_streak),
+        longest_win_streak,
+        peak,
+        recent_games,
+        frequent_opponents,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::skill_base::GameId;
+
+    fn game(id: &str, winner_ids: &[&str], loser_ids: &[&str]) -> Game {
+        let ids = |names: &[&str]| {
+            names
+                .iter()
+                .map(|name| UserId::from(name.to_string()))
+                .collect::<Vec<_>>()
+        };
+        Game::new(
+            GameId::from(id.to_owned()),
+            chrono::Utc::now(),
+            ids(winner_ids),
+            ids(loser_ids),
+        )
+    }
+
+    #[test]
+    fn test_head_to_head() {
+        let games = vec![
+            game("4", &["a"], &["b"]),
+            game("3", &["a", "b"], &["c", "d"]),
+            game("2", &["b", "c"], &["a", "d"]),
+            game("1", &["c"], &["d"]),
+            game("0", &["b"], &["a"]),
+        ];
+        let a = UserId::from("a".to_owned());
+        let b = UserId::from("b".to_owned());
+        let head_to_head = head_to_head(&a, &b, &games, 0.5);
+
+        assert_eq!(head_to_head.against, Record { wins: 1, losses: 2 });
+        assert_eq!(head_to_head.together, Record { wins: 1, losses: 0 });
+        assert_eq!(
+            head_to_head
+                .last_meetings
+                .iter()
+                .map(|game| game.id().clone())
+                .collect::<Vec<_>>(),
+            ["4", "2", "0"]
+                .iter()
+                .map(|id| GameId::from(id.to_string()))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_pair_statistics() {
+        let games = vec![
+            game("2", &["a", "b"], &["c", "d"]),
+            game("1", &["b", "a"], &["c", "e"]),
+            game("0", &["c", "d"], &["a", "b"]),
+        ];
+        let mut pairs = pair_statistics(&games, |game| {
+            if game.id() == &GameId::from("2".to_owned()) {
+                Some(0.25)
+            } else {
+                None
+            }
+        });
+        pairs.sort_by_key(|pair| std::cmp::Reverse(pair.record.games()));
+
+        let a = UserId::from("a".to_owned());
+        let b = UserId::from("b".to_owned());
+        assert_eq!(pairs.len(), 3);
+        assert_eq!(pairs[0].user_ids, (a, b));
+        assert_eq!(pairs[0].record, Record { wins: 2, losses: 1 });
+        assert!((pairs[0].performance - 0.75).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_opponent_statistics() {
+        let games = vec![
+            game("1", &["a", "b"], &["c", "d"]),
+            game("0", &["c"], &["a"]),
+        ];
+        let a = UserId::from("a".to_owned());
+        let c = UserId::from("c".to_owned());
+        let opponents = opponent_statistics(&a, &games, |_| Some(0.5));
+
+        assert_eq!(opponents.len(), 2);
+        let opponent_c = opponents
+            .iter()
+            .find(|opponent| opponent.opponent_id == c)
+            .unwrap();
+        assert_eq!(opponent_c.record, Record { wins: 1, losses: 1 });
+        assert!(opponent_c.performance.abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_user_statistics() {
+        let games = vec![
+            game("5", &["a"], &["b"]),
+            game("4", &["a"], &["c"]),
+            game("3", &["b"], &["a"]),
+            game("2", &["a"], &["b"]),
+            game("1", &["a", "d"], &["b", "c"]),
+            game("0", &["a"], &["b"]),
+        ];
+        let a = UserId::from("a".to_owned());
+        let statistics = user_statistics(
+            &a,
+            &games,
+            &(chrono::Utc::now() - chrono::Duration::days(30)),
+            |message| message.to_mu_sigma2().0,
+            |_| None,
+        );
+
+        assert_eq!(statistics.record, Record { wins: 5, losses: 1 });
+        assert_eq!(statistics.current_win_streak, 2);
+        assert_eq!(statistics.longest_win_streak, 3);
+        assert!(statistics.peak.is_none());
+        assert_eq!(statistics.recent_games, 6);
+        assert_eq!(
+            statistics
+                .frequent_opponents
+                .iter()
+                .map(|opponent| opponent.opponent_id.clone())
+                .collect::<Vec<_>>(),
+            vec![UserId::from("b".to_owned()), UserId::from("c".to_owned())]
+        );
+    }
+}
@@ -0,0 +1,570 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::skill_base::{GameId, TournamentId, UserId};
+
+/// Minimal number of entrants of a tournament.
+pub const MIN_ENTRANTS: usize = 2;
+
+/// How entrants drop out of a tournament.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Format {
+    /// Entrants drop out after their first loss.
+    SingleElimination,
+    /// Entrants drop out after their second loss.
+    DoubleElimination,
+}
+
+/// Part of a tournament a match belongs to.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Bracket {
+    Winners,
+    Losers,
+    /// Match between the winners of the winners and the losers bracket.
+    GrandFinal,
+}
+
+/// Occupant of one side of a match.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(tag = "type", content = "entrant", rename_all = "snake_case")]
+pub enum Slot {
+    /// Waits for the result of an earlier match.
+    Pending,
+    /// Nobody plays on this side, so the opponent advances.
+    Bye,
+    /// Index of the entrant that plays on this side.
+    Entrant(usize),
+}
+
+/// Side of a match an entrant moves to.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Target {
+    pub match_index: usize,
+    pub side: usize,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Match {
+    pub bracket: Bracket,
+    /// Round within the bracket starting at 1.
+    pub round: usize,
+    pub slots: [Slot; 2],
+    /// Side that won the match once it is decided.
+    pub winner: Option<usize>,
+    /// Game that decided the match, if it was not decided by a bye.
+    pub game_id: Option<GameId>,
+    /// Where the winner moves to. The winner of the final match moves nowhere.
+    pub winner_to: Option<Target>,
+    /// Where the loser moves to. The loser drops out if this is `None`.
+    pub loser_to: Option<Target>,
+}
+
+impl Match {
+    fn new(bracket: Bracket, round: usize) -> Self {
+        Match {
+            bracket,
+            round,
+            slots: [Slot::Pending, Slot::Pending],
+            winner: None,
+            game_id: None,
+            winner_to: None,
+            loser_to: None,
+        }
+    }
+}
+
+/// Describes why a tournament cannot be created or a match cannot be
+/// recorded.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+#[serde(tag = "rule", rename_all = "snake_case")]
+pub enum TournamentError {
+    /// The tournament has fewer than `min_entrants` entrants.
+    TooFewEntrants { min_entrants: usize },
+    /// An entrant has no players.
+    EmptyEntrant { entrant: usize },
+    /// A user plays for more than one entrant or more than once.
+    DuplicatePlayer { user_id: UserId },
+    /// The tournament has no match with this index.
+    UnknownMatch { match_index: usize },
+    /// The match still waits for the results of earlier matches.
+    MatchNotReady { match_index: usize },
+    /// The match already has a winner.
+    MatchDecided { match_index: usize },
+    /// A match only has the sides 0 and 1.
+    InvalidSide { side: usize },
+}
+
+impl fmt::Display for TournamentError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TournamentError::TooFewEntrants { min_entrants } => {
+                write!(f, "tournament needs at least {} entrants", min_entrants)
+            }
+            TournamentError::EmptyEntrant { entrant } => {
+                write!(f, "entrant {} has no players", entrant)
+            }
+            TournamentError::DuplicatePlayer { ref user_id } => {
+                write!(f, "user {:?} plays more than once", user_id)
+            }
+            TournamentError::UnknownMatch { match_index } => {
+                write!(f, "match {} does not exist", match_index)
+            }
+            TournamentError::MatchNotReady { match_index } => {
+                write!(f, "match {} waits for earlier matches", match_index)
+            }
+            TournamentError::MatchDecided { match_index } => {
+                write!(f, "match {} already has a winner", match_index)
+            }
+            TournamentError::InvalidSide { side } => write!(f, "side {} does not exist", side),
+        }
+    }
+}
+
+/// Knockout tournament between players or teams.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Tournament {
+    id: TournamentId,
+    name: String,
+    format: Format,
+    /// Players of all entrants ordered by seed, the strongest first.
+    entrants: Vec<Vec<UserId>>,
+    matches: Vec<Match>,
+}
+
+impl Tournament {
+    /// Creates a tournament and its bracket. Strong seeds get byes if the
+    /// number of entrants is not a power of two.
+    ///
+    /// A double elimination tournament ends with a single grand final game.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` ID of the tournament.
+    /// * `name` name of the tournament.
+    /// * `format` how entrants drop out.
+    /// * `entrants` players of all entrants ordered by seed, the strongest
+    ///   first.
+    pub fn new(
+        id: TournamentId,
+        name: String,
+        format: Format,
+        entrants: Vec<Vec<UserId>>,
+    ) -> Result<Self, TournamentError> {
+        Tournament::validate_entrants(&entrants)?;
+
+        let mut tournament = Tournament {
+            id,
+            name,
+            format,
+            matches: Vec::new(),
+            entrants,
+        };
+        tournament.build_bracket();
+        tournament.advance_byes();
+        Ok(tournament)
+    }
+
+    /// Checks that there are enough entrants and that every user plays for
+    /// exactly one of them.
+    pub fn validate_entrants(entrants: &[Vec<UserId>]) -> Result<(), TournamentError> {
+        if entrants.len() < MIN_ENTRANTS {
+            return Err(TournamentError::TooFewEntrants {
+                min_entrants: MIN_ENTRANTS,
+            });
+        }
+        if let Some(entrant) = entrants.iter().position(|user_ids| user_ids.is_empty()) {
+            return Err(TournamentError::EmptyEntrant { entrant });
+        }
+        let mut seen = HashSet::new();
+        if let Some(user_id) = entrants
+            .iter()
+            .flatten()
+            .find(|user_id| !seen.insert(*user_id))
+        {
+            return Err(TournamentError::DuplicatePlayer {
+                user_id: user_id.clone(),
+            });
+        }
+        Ok(())
+    }
+
+    pub fn id(&self) -> &TournamentId {
+        &self.id
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    pub fn entrants(&self) -> &Vec<Vec<UserId>> {
+        &self.entrants
+    }
+
+    pub fn matches(&self) -> &Vec<Match> {
+        &self.matches
+    }
+
+    /// Returns the players of the winner of the tournament once the final
+    /// match is decided.
+    pub fn champion(&self) -> Option<&Vec<UserId>> {
+        let last = self.matches.last()?;
+        match last.slots[last.winner?] {
+            Slot::Entrant(entrant) => Some(&self.entrants[entrant]),
+            _ => None,
+        }
+    }
+
+    /// Records the result of a match and moves both entrants on.
+    ///
+    /// Returns the players of the winning and the losing entrant.
+    ///
+    /// # Arguments
+    ///
+    /// * `match_index` index of the match.
+    /// * `winner` side that won the match.
+    /// * `game_id` ID of the game that decided the match.
+    pub fn record(
+        &mut self,
+        match_index: usize,
+        winner: usize,
+        game_id: GameId,
+    ) -> Result<(Vec<UserId>, Vec<UserId>), TournamentError> {
+        if winner > 1 {
+            return Err(TournamentError::InvalidSide { side: winner });
+        }
+        let played = self
+            .matches
+            .get(match_index)
+            .ok_or(TournamentError::UnknownMatch { match_index })?;
+        if played.winner.is_some() {
+            return Err(TournamentError::MatchDecided { match_index });
+        }
+        let (winner_entrant, loser_entrant) = match (played.slots[winner], played.slots[1 - winner])
+        {
+            (Slot::Entrant(winner_entrant), Slot::Entrant(loser_entrant)) => {
+                (winner_entrant, loser_entrant)
+            }
+            _ => return Err(TournamentError::MatchNotReady { match_index }),
+        };
+
+        self.matches[match_index].game_id = Some(game_id);
+        self.decide(match_index, winner);
+        self.advance_byes();
+        Ok((
+            self.entrants[winner_entrant].clone(),
+            self.entrants[loser_entrant].clone(),
+        ))
+    }
+
+    /// Adds all matches of the bracket, so that the final match comes last.
+    fn build_bracket(&mut self) {
+        let size = self.entrants.len().next_power_of_two();
+        let rounds = size.trailing_zeros() as usize;
+
+        let winners = (1..=rounds)
+            .map(|round| self.add_round(Bracket::Winners, round, size >> round))
+            .collect::<Vec<_>>();
+        for (index, seeds) in seed_order(size).chunks(2).enumerate() {
+            for (side, seed) in seeds.iter().enumerate() {
+                self.matches[winners[0][index]].slots[side] = if *seed < self.entrants.len() {
+                    Slot::Entrant(*seed)
+                } else {
+                    Slot::Bye
+                };
+            }
+        }
+        for round in 1..rounds {
+            for (index, match_index) in winners[round - 1].iter().enumerate() {
+                self.matches[*match_index].winner_to = Some(Target {
+                    match_index: winners[round][index / 2],
+                    side: index % 2,
+                });
+            }
+        }
+
+        if self.format == Format::SingleElimination {
+            return;
+        }
+
+        // The losers bracket alternates between rounds that take in the
+        // losers of the winners bracket and rounds that halve the remaining
+        // entrants.
+        let losers = (1..=2 * (rounds - 1))
+            .map(|round| self.add_round(Bracket::Losers, round, size >> (round.div_ceil(2) + 1)))
+            .collect::<Vec<_>>();
+        for (index, match_index) in winners[0].iter().enumerate() {
+            if let Some(losers_round) = losers.first() {
+                self.matches[*match_index].loser_to = Some(Target {
+                    match_index: losers_round[index / 2],
+                    side: index % 2,
+                });
+            }
+        }
+        for round in 2..=rounds {
+            // Losers drop in reversed order to avoid early rematches.
+            let losers_round = &losers[2 * (round - 1) - 1];
+            for (index, match_index) in winners[round - 1].iter().enumerate() {
+                self.matches[*match_index].loser_to = Some(Target {
+                    match_index: losers_round[losers_round.len() - 1 - index],
+                    side: 1,
+                });
+            }
+        }
+        for round in 1..losers.len() {
+            for (index, match_index) in losers[round - 1].iter().enumerate() {
+                self.matches[*match_index].winner_to = Some(if round % 2 == 1 {
+                    Target {
+                        match_index: losers[round][index],
+                        side: 0,
+                    }
+                } else {
+                    Target {
+                        match_index: losers[round][index / 2],
+                        side: index % 2,
+                    }
+                });
+            }
+        }
+
+        let grand_final = self.add_round(Bracket::GrandFinal, 1, 1)[0];
+        let winners_final = winners[rounds - 1][0];
+        self.matches[winners_final].winner_to = Some(Target {
+            match_index: grand_final,
+            side: 0,
+        });
+        let losers_final = Target {
+            match_index: grand_final,
+            side: 1,
+        };
+        match losers.last() {
+            Some(losers_round) => self.matches[losers_round[0]].winner_to = Some(losers_final),
+            // Without a losers bracket the loser of the winners final gets a
+            // second chance right away.
+            None => self.matches[winners_final].loser_to = Some(losers_final),
+        }
+    }
+
+    /// Adds the matches of a round and returns their indices.
+    fn add_round(&mut self, bracket: Bracket, round: usize, count: usize) -> Vec<usize> {
+        (0..count)
+            .map(|_| {
+                self.matches.push(Match::new(bracket, round));
+                self.matches.len() - 1
+            })
+            .collect()
+    }
+
+    /// Sets the winner of a match and moves both sides on.
+    fn decide(&mut self, match_index: usize, winner: usize) {
+        let decided = &mut self.matches[match_index];
+        decided.winner = Some(winner);
+        let moves = [
+            (decided.winner_to, decided.slots[winner]),
+            (decided.loser_to, decided.slots[1 - winner]),
+        ];
+        for (target, slot) in moves {
+            if let Some(target) = target {
+                self.matches[target.match_index].slots[target.side] = slot;
+            }
+        }
+    }
+
+    /// Decides all matches with a bye, until every undecided match waits for
+    /// players or can be played.
+    fn advance_byes(&mut self) {
+        loop {
+            let bye = self.matches.iter().enumerate().find_map(|(index, m)| {
+                if m.winner.is_some() {
+                    return None;
+                }
+                match m.slots {
+                    [Slot::Pending, _] | [_, Slot::Pending] => None,
+                    [Slot::Bye, _] => Some((index, 1)),
+                    [_, Slot::Bye] => Some((index, 0)),
+                    _ => None,
+                }
+            });
+            match bye {
+                Some((index, winner)) => self.decide(index, winner),
+                None => break,
+            }
+        }
+    }
+}
+
+/// Orders the seeds of a bracket of `size` entrants such that neighbouring
+/// seeds play each other in the first round and strong seeds meet as late as
+/// possible.
+fn seed_order(size: usize) -> Vec<usize> {
+    let mut order = vec![0];
+    while order.len() < size {
+        let count = order.len() * 2;
+        order = order
+            .iter()
+            .flat_map(|seed| [*seed, count - 1 - *seed])
+            .collect();
+    }
+    order
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entrants(count: usize) -> Vec<Vec<UserId>> {
+        (0..count)
+            .map(|index| vec![UserId::from(format!("user{}", index))])
+            .collect()
+    }
+
+    fn tournament(format: Format, count: usize) -> Tournament {
+        Tournament::new(
+            TournamentId::from("cup".to_owned()),
+            "Cup".to_owned(),
+            format,
+            entrants(count),
+        )
+        .unwrap()
+    }
+
+    fn game_id() -> GameId {
+        GameId::from("game".to_owned())
+    }
+
+    /// Returns the index of the first match that can be played.
+    fn playable(tournament: &Tournament) -> Option<usize> {
+        tournament.matches().iter().position(|m| {
+            m.winner.is_none() && matches!(m.slots, [Slot::Entrant(_), Slot::Entrant(_)])
+        })
+    }
+
+    #[test]
+    fn test_seed_order() {
+        assert_eq!(seed_order(1), vec![0]);
+        assert_eq!(seed_order(4), vec![0, 3, 1, 2]);
+        assert_eq!(seed_order(8), vec![0, 7, 3, 4, 1, 6, 2, 5]);
+    }
+
+    #[test]
+    fn test_new_invalid() {
+        let new = |entrants| {
+            Tournament::new(
+                TournamentId::from("cup".to_owned()),
+                "Cup".to_owned(),
+                Format::SingleElimination,
+                entrants,
+            )
+            .map(|_| ())
+        };
+        assert_eq!(
+            new(entrants(1)),
+            Err(TournamentError::TooFewEntrants {
+                min_entrants: MIN_ENTRANTS
+            })
+        );
+        assert_eq!(
+            new(vec![vec![], vec![UserId::from("a".to_owned())]]),
+            Err(TournamentError::EmptyEntrant { entrant: 0 })
+        );
+        let a = UserId::from("a".to_owned());
+        assert_eq!(
+            new(vec![vec![a.clone()], vec![a.clone()]]),
+            Err(TournamentError::DuplicatePlayer { user_id: a })
+        );
+    }
+
+    #[test]
+    fn test_single_elimination_byes() {
+        let mut tournament = tournament(Format::SingleElimination, 3);
+        assert_eq!(tournament.matches().len(), 3);
+        // The top seed has a bye and waits in the final.
+        assert_eq!(tournament.matches()[0].winner, Some(0));
+        assert_eq!(tournament.matches()[2].slots[0], Slot::Entrant(0));
+
+        let index = playable(&tournament).unwrap();
+        assert_eq!(index, 1);
+        assert_eq!(
+            tournament.record(index, 1, game_id()),
+            Ok((entrants(3)[2].clone(), entrants(3)[1].clone()))
+        );
+        assert_eq!(
+            tournament.record(index, 0, game_id()),
+            Err(TournamentError::MatchDecided { match_index: 1 })
+        );
+
+        assert!(tournament.champion().is_none());
+        tournament.record(2, 0, game_id()).unwrap();
+        assert_eq!(tournament.champion(), Some(&entrants(3)[0]));
+    }
+
+    #[test]
+    fn test_record_invalid() {
+        let mut tournament = tournament(Format::SingleElimination, 4);
+        assert_eq!(
+            tournament.record(2, 0, game_id()),
+            Err(TournamentError::MatchNotReady { match_index: 2 })
+        );
+        assert_eq!(
+            tournament.record(3, 0, game_id()),
+            Err(TournamentError::UnknownMatch { match_index: 3 })
+        );
+        assert_eq!(
+            tournament.record(0, 2, game_id()),
+            Err(TournamentError::InvalidSide { side: 2 })
+        );
+    }
+
+    #[test]
+    fn test_double_elimination() {
+        let mut tournament = tournament(Format::DoubleElimination, 4);
+        let brackets = tournament
+            .matches()
+            .iter()
+            .map(|m| m.bracket)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            brackets,
+            vec![
+                Bracket::Winners,
+                Bracket::Winners,
+                Bracket::Winners,
+                Bracket::Losers,
+                Bracket::Losers,
+                Bracket::GrandFinal
+            ]
+        );
+
+        // The higher seed wins every match except the grand final.
+        while let Some(index) = playable(&tournament) {
+            let played = &tournament.matches()[index];
+            let winner = match (played.bracket, played.slots) {
+                (Bracket::GrandFinal, _) => 1,
+                (_, [Slot::Entrant(first), Slot::Entrant(second)]) if second < first => 1,
+                _ => 0,
+            };
+            tournament.record(index, winner, game_id()).unwrap();
+        }
+        // The runner-up of the winners bracket comes back through the losers
+        // bracket and takes the cup.
+        assert_eq!(tournament.champion(), Some(&entrants(4)[1]));
+        assert!(tournament.matches().iter().all(|m| m.winner.is_some()));
+    }
+
+    #[test]
+    fn test_double_elimination_byes() {
+        let mut tournament = tournament(Format::DoubleElimination, 5);
+        while let Some(index) = playable(&tournament) {
+            tournament.record(index, 0, game_id()).unwrap();
+        }
+        assert_eq!(tournament.champion(), Some(&entrants(5)[0]));
+        assert!(tournament.matches().iter().all(|m| m.winner.is_some()));
+    }
+}
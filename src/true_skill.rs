@@ -1,6 +1,22 @@
 use std::f64;
 
 use crate::message::Message;
+use crate::player::Player;
+use crate::rating_system::RatingSystem;
+
+/// Message that carries no information.
+const UNIFORM: Message = Message { pi: 0.0, tau: 0.0 };
+
+/// Standardized performance difference below which the chance of a win is
+/// too small to divide by, so that the truncation uses an asymptotic
+/// expansion instead.
+const ASYMPTOTIC_LIMIT: f64 = -30.0;
+
+/// Maximal number of sweeps of expectation propagation.
+const MAX_ITERATIONS: usize = 30;
+
+/// Largest change of a message at which expectation propagation stops.
+const CONVERGENCE_THRESHOLD: f64 = 1e-6;
 
 pub enum GameResult {
     Won,
@@ -20,7 +36,7 @@ impl TrueSkill {
     /// # Arguments
     ///
     /// * `beta` standard deviation of the sampled game skill from a player's
-    ///    skill.
+    ///   skill.
     /// * `eps` draw margin around 0.
     pub fn new(beta: f64, eps: f64) -> Self {
         TrueSkill { beta, eps }
@@ -36,6 +52,18 @@ impl TrueSkill {
     }
 
     fn pass_weighted(weighted_messages: &[(f64, Message)]) -> Message {
+        // Messages without weight do not count, and a sum with a part that is
+        // completely unknown is unknown itself.
+        let weighted_messages = weighted_messages
+            .iter()
+            .filter(|(weight, _)| *weight != 0.0)
+            .collect::<Vec<_>>();
+        if !weighted_messages
+            .iter()
+            .all(|(_, message)| message.pi > 0.0)
+        {
+            return UNIFORM;
+        }
         // TODO(mkiefel): this could potentially also a fold and take an iterator as
         // input.
         let pi = 1.0
@@ -51,15 +79,6 @@ impl TrueSkill {
         Message { pi, tau }
     }
 
-    fn pass_from_performance(messages: &[Message]) -> Message {
-        TrueSkill::pass_weighted(
-            &messages
-                .iter()
-                .map(|message| (1.0, *message))
-                .collect::<Vec<(f64, Message)>>(),
-        )
-    }
-
     fn pass_to_difference(left: Message, right: Message) -> Message {
         let difference_messages = [(1.0, left), (-1.0, right)];
         TrueSkill::pass_weighted(&difference_messages)
@@ -70,34 +89,73 @@ impl TrueSkill {
     }
 
     fn norm_cdf(x: f64) -> f64 {
-        0.5 * (1.0 + libm::erf(x / 2.0_f64.sqrt()))
+        // The complementary error function stays precise far into the lower
+        // tail, where `1 + erf` cancels to 0.
+        0.5 * libm::erfc(-x / 2.0_f64.sqrt())
+    }
+
+    /// Returns `v = pdf(x) / cdf(x)` for a won game together with `v + x`.
+    fn won_terms(x: f64) -> (f64, f64) {
+        if x >= ASYMPTOTIC_LIMIT {
+            let v = TrueSkill::norm_pdf(x) / TrueSkill::norm_cdf(x);
+            return (v, v + x);
+        }
+        // Asymptotic expansion of the Mills ratio. It also gives `v + x`
+        // without cancellation.
+        let z = -x;
+        let series = 1.0 - z.powi(-2) + 3.0 * z.powi(-4) - 15.0 * z.powi(-6);
+        (
+            z / series,
+            (z.powi(-1) - 3.0 * z.powi(-3) + 15.0 * z.powi(-5)) / series,
+        )
     }
 
     fn difference_marginal_won(&self, message: &Message) -> Message {
         fn v(t: f64, eps: f64) -> f64 {
-            TrueSkill::norm_pdf(t - eps) / TrueSkill::norm_cdf(t - eps)
+            TrueSkill::won_terms(t - eps).0
         }
 
         fn w(t: f64, eps: f64) -> f64 {
-            let v_value = v(t, eps);
-            v_value * (v_value + t - eps)
+            let (v_value, v_plus_x) = TrueSkill::won_terms(t - eps);
+            v_value * v_plus_x
         }
 
         self.difference_marginal(v, w, message)
     }
 
     fn difference_marginal_draw(&self, message: &Message) -> Message {
+        // `v` is odd and `w` is even in `t`, so both get evaluated for positive
+        // `t` only, where the distribution functions are precise.
+        fn draw_probability(t: f64, eps: f64) -> f64 {
+            TrueSkill::norm_cdf(eps - t) - TrueSkill::norm_cdf(-eps - t)
+        }
+
         fn v(t: f64, eps: f64) -> f64 {
-            (TrueSkill::norm_pdf(-eps - t) - TrueSkill::norm_pdf(eps - t))
-                / (TrueSkill::norm_cdf(eps - t) - TrueSkill::norm_cdf(-eps - t))
+            let s = t.abs();
+            let probability = draw_probability(s, eps);
+            let v_value = if probability > 0.0 {
+                (TrueSkill::norm_pdf(-eps - s) - TrueSkill::norm_pdf(eps - s)) / probability
+            } else {
+                eps - s
+            };
+            if t < 0.0 {
+                -v_value
+            } else {
+                v_value
+            }
         }
 
         fn w(t: f64, eps: f64) -> f64 {
-            let v_value = v(t, eps);
-            v_value.powi(2)
-                + ((eps - t) * TrueSkill::norm_pdf(eps - t)
-                    + (eps + t) * TrueSkill::norm_pdf(eps + t))
-                    / (TrueSkill::norm_cdf(eps - t) - TrueSkill::norm_cdf(-eps - t))
+            let s = t.abs();
+            let probability = draw_probability(s, eps);
+            if probability > 0.0 {
+                v(s, eps).powi(2)
+                    + ((eps - s) * TrueSkill::norm_pdf(eps - s)
+                        + (eps + s) * TrueSkill::norm_pdf(eps + s))
+                        / probability
+            } else {
+                1.0
+            }
         }
 
         self.difference_marginal(v, w, message)
@@ -115,7 +173,9 @@ impl TrueSkill {
         let sqrt_c = c.sqrt();
 
         let v_value = v(d / sqrt_c, self.eps * sqrt_c);
-        let w_value = 1.0 - w(d / sqrt_c, self.eps * sqrt_c);
+        // Rounding must not turn the variance of the truncated distribution
+        // zero or negative.
+        let w_value = (1.0 - w(d / sqrt_c, self.eps * sqrt_c)).max(f64::EPSILON);
 
         Message {
             pi: c / w_value,
@@ -136,80 +196,549 @@ impl TrueSkill {
         )
     }
 
+    /// Passes the message from the team performance back to every weighted
+    /// player performance. Players that did not play get no update.
     fn pass_to_performance(
-        from_performance_messages: &[Message],
+        from_performance_messages: &[(f64, Message)],
         update_message: &Message,
     ) -> Vec<Message> {
-        let mut weighted_messages = from_performance_messages
+        from_performance_messages
             .iter()
-            .map(|message| (-1.0, *message))
-            .collect::<Vec<(f64, Message)>>();
-        let mut out_messages: Vec<Message> =
-            vec![Message { pi: 0.0, tau: 0.0 }; from_performance_messages.len()];
-        for i in 0..weighted_messages.len() {
-            weighted_messages[i].0 = 1.0;
-            weighted_messages[i].1 = *update_message;
-            out_messages[i] = TrueSkill::pass_weighted(&weighted_messages);
-            weighted_messages[i].0 = -1.0;
-            weighted_messages[i].1 = from_performance_messages[i];
-        }
-        out_messages
+            .enumerate()
+            .map(|(i, (weight, _))| {
+                if *weight == 0.0 {
+                    return UNIFORM;
+                }
+                // Solves the weighted sum for the performance of this player.
+                let weighted_messages = from_performance_messages
+                    .iter()
+                    .enumerate()
+                    .map(|(j, (other_weight, message))| {
+                        if i == j {
+                            (1.0 / weight, *update_message)
+                        } else {
+                            (-other_weight / weight, *message)
+                        }
+                    })
+                    .collect::<Vec<_>>();
+                TrueSkill::pass_weighted(&weighted_messages)
+            })
+            .collect()
     }
 
     fn to_skill(&self, message: &Message) -> Message {
         self.pass_from_skill(message)
     }
 
+    /// Returns the mean and the variance of the difference in performance
+    /// between the left and the right team.
+    fn performance_difference(&self, left_team: &[Message], right_team: &[Message]) -> (f64, f64) {
+        let team_mu_sigma2 = |team: &[Message]| {
+            team.iter()
+                .map(|message| message.to_mu_sigma2())
+                .fold((0.0, 0.0), |(mu, sigma2), (player_mu, player_sigma2)| {
+                    (mu + player_mu, sigma2 + player_sigma2)
+                })
+        };
+        let (left_mu, left_sigma2) = team_mu_sigma2(left_team);
+        let (right_mu, right_sigma2) = team_mu_sigma2(right_team);
+        let players = (left_team.len() + right_team.len()) as f64;
+        (
+            left_mu - right_mu,
+            players * self.beta.powi(2) + left_sigma2 + right_sigma2,
+        )
+    }
+
+    /// Returns the probability that the left team wins against the right team.
+    pub fn win_probability(&self, left_team: &[Message], right_team: &[Message]) -> f64 {
+        let (mu, sigma2) = self.performance_difference(left_team, right_team);
+        TrueSkill::norm_cdf((mu - self.eps) / sigma2.sqrt())
+    }
+
+    /// Returns the match quality of a game between the two teams, which is
+    /// the chance of a draw relative to the chance of a draw between equally
+    /// skilled teams. It lies between 0 and 1 and higher is fairer.
+    pub fn match_quality(&self, left_team: &[Message], right_team: &[Message]) -> f64 {
+        let (mu, sigma2) = self.performance_difference(left_team, right_team);
+        let players = (left_team.len() + right_team.len()) as f64;
+        let beta2 = players * self.beta.powi(2);
+        (beta2 / sigma2).sqrt() * (-mu.powi(2) / (2.0 * sigma2)).exp()
+    }
+
     /// Passes all input team messages down the message tree and returns the
-    /// message update for each player.
+    /// message update for each player. Every player played the whole game.
     pub fn tree_pass(
         &self,
         left_team: &[Message],
         right_team: &[Message],
         result: GameResult,
     ) -> (Vec<Message>, Vec<Message>) {
-        if let GameResult::Lost = result {
-            let result = self.tree_pass(right_team, left_team, GameResult::Won);
-            return (result.1, result.0);
+        let ranks = match result {
+            GameResult::Won => [0, 1],
+            GameResult::Draw => [0, 0],
+            GameResult::Lost => [1, 0],
+        };
+        let full_time = |team: &[Message]| {
+            team.iter()
+                .map(|message| (1.0, *message))
+                .collect::<Vec<_>>()
+        };
+        let mut updates = self.rank_pass(&[full_time(left_team), full_time(right_team)], &ranks);
+        let right_updates = updates.pop().unwrap();
+        let left_updates = updates.pop().unwrap();
+        (left_updates, right_updates)
+    }
+
+    /// Rates a game between any number of teams and returns the message
+    /// update for each player.
+    ///
+    /// Teams are compared with their neighbours in the ranking. As soon as
+    /// there are more than two teams the factor graph has loops, so
+    /// expectation propagation runs along the chain of neighbours until the
+    /// messages converge.
+    ///
+    /// # Arguments
+    ///
+    /// * `teams` skills of the players of every team together with the
+    ///   fraction of the game each of them played. Every team needs at
+    ///   least one player with a positive fraction.
+    /// * `ranks` rank of every team, lower is better. Teams with the same
+    ///   rank drew.
+    pub fn rank_pass(&self, teams: &[Vec<(f64, Message)>], ranks: &[usize]) -> Vec<Vec<Message>> {
+        self.rank_pass_with_offsets(teams, &vec![None; teams.len()], ranks)
+            .0
+    }
+
+    /// Rates a game like `rank_pass`, where the performance of some teams
+    /// includes an offset, e.g. the advantage of their side of the table.
+    ///
+    /// Returns the message update for each player and for each offset.
+    ///
+    /// # Arguments
+    ///
+    /// * `teams` skills of the players of every team together with the
+    ///   fraction of the game each of them played.
+    /// * `offsets` belief of the offset that is added to the performance of
+    ///   every team, if any.
+    /// * `ranks` rank of every team, lower is better.
+    pub fn rank_pass_with_offsets(
+        &self,
+        teams: &[Vec<(f64, Message)>],
+        offsets: &[Option<Message>],
+        ranks: &[usize],
+    ) -> (Vec<Vec<Message>>, Vec<Option<Message>>) {
+        if teams.len() < 2 {
+            return (
+                teams.iter().map(|team| vec![UNIFORM; team.len()]).collect(),
+                offsets
+                    .iter()
+                    .map(|offset| offset.map(|_| UNIFORM))
+                    .collect(),
+            );
         }
 
-        let left_performances = left_team
+        let mut order = (0..teams.len()).collect::<Vec<_>>();
+        order.sort_by_key(|team| ranks[*team]);
+        // Offsets are part of the team performance without any noise.
+        let performances = order
             .iter()
-            .map(|message| self.pass_from_skill(message))
+            .map(|team| {
+                teams[*team]
+                    .iter()
+                    .map(|(weight, skill)| (*weight, self.pass_from_skill(skill)))
+                    .chain(offsets[*team].map(|offset| (1.0, offset)))
+                    .collect::<Vec<_>>()
+            })
             .collect::<Vec<_>>();
-
-        let right_performances = right_team
+        let team_performances = performances
             .iter()
-            .map(|message| self.pass_from_skill(message))
+            .map(|performances| TrueSkill::pass_weighted(performances))
             .collect::<Vec<_>>();
 
-        let left_performance = TrueSkill::pass_from_performance(&left_performances);
-        let right_performance = TrueSkill::pass_from_performance(&right_performances);
+        // Messages from the difference between the k-th and the next team
+        // back to the better and to the worse of the two teams.
+        let differences = teams.len() - 1;
+        let mut to_better = vec![UNIFORM; differences];
+        let mut to_worse = vec![UNIFORM; differences];
+        for _ in 0..MAX_ITERATIONS {
+            let mut change = 0.0_f64;
+            for k in (0..differences).chain((0..differences).rev()) {
+                let better = match k {
+                    0 => team_performances[k],
+                    _ => team_performances[k].include(&to_worse[k - 1]),
+                };
+                let worse = match to_better.get(k + 1) {
+                    Some(message) => team_performances[k + 1].include(message),
+                    None => team_performances[k + 1],
+                };
 
-        let to_difference_message =
-            TrueSkill::pass_to_difference(left_performance, right_performance);
-        let marginal = match result {
-            GameResult::Won => self.difference_marginal_won(&to_difference_message),
-            GameResult::Draw => self.difference_marginal_draw(&to_difference_message),
-            _ => panic!("cannot have Lost here"),
-        };
+                let to_difference_message = TrueSkill::pass_to_difference(better, worse);
+                let marginal = if ranks[order[k]] == ranks[order[k + 1]] {
+                    self.difference_marginal_draw(&to_difference_message)
+                } else {
+                    self.difference_marginal_won(&to_difference_message)
+                };
+                // Keeps the last messages if rounding leaves no belief to pass
+                // on.
+                let from_difference = match marginal.exclude(&to_difference_message) {
+                    Some(message) => message,
+                    None => continue,
+                };
+                let (better_message, worse_message) =
+                    TrueSkill::pass_from_difference(better, worse, from_difference);
 
-        let from_difference_message = TrueSkill::pass_from_difference(
-            left_performance,
-            right_performance,
-            marginal.exclude(&to_difference_message),
-        );
+                change = change
+                    .max(TrueSkill::message_change(&to_better[k], &better_message))
+                    .max(TrueSkill::message_change(&to_worse[k], &worse_message));
+                to_better[k] = better_message;
+                to_worse[k] = worse_message;
+            }
+            if change < CONVERGENCE_THRESHOLD {
+                break;
+            }
+        }
 
-        let left_skills =
-            TrueSkill::pass_to_performance(&left_performances, &from_difference_message.0)
+        let mut updates = vec![Vec::new(); teams.len()];
+        let mut offset_updates = vec![None; teams.len()];
+        for (k, team) in order.iter().enumerate() {
+            let mut from_differences = UNIFORM;
+            if k > 0 {
+                from_differences = from_differences.include(&to_worse[k - 1]);
+            }
+            if k < differences {
+                from_differences = from_differences.include(&to_better[k]);
+            }
+            let mut messages = TrueSkill::pass_to_performance(&performances[k], &from_differences);
+            if offsets[*team].is_some() {
+                offset_updates[*team] = messages.pop();
+            }
+            updates[*team] = messages
                 .iter()
                 .map(|message| self.to_skill(message))
-                .collect::<Vec<_>>();
-        let right_skills =
-            TrueSkill::pass_to_performance(&right_performances, &from_difference_message.1)
+                .collect();
+        }
+        (updates, offset_updates)
+    }
+
+    /// Rates a game between two teams like `RatingSystem::rate_weighted`,
+    /// where the performance of either team may include an offset, e.g. the
+    /// advantage of their side of the table.
+    ///
+    /// Returns the new skills of the winners and the losers together with the
+    /// new beliefs of the offsets.
+    ///
+    /// # Arguments
+    ///
+    /// * `winners` skills of the winners together with the fraction of the
+    ///   game each of them played.
+    /// * `losers` skills of the losers together with the fraction of the game
+    ///   each of them played.
+    /// * `offsets` belief of the offset of the winners and of the losers, if
+    ///   any.
+    pub fn rate_with_offsets(
+        &self,
+        winners: &[(f64, Message)],
+        losers: &[(f64, Message)],
+        offsets: [Option<Message>; 2],
+    ) -> (Vec<Message>, Vec<Message>, [Option<Message>; 2]) {
+        let (updates, offset_updates) =
+            self.rank_pass_with_offsets(&[winners.to_vec(), losers.to_vec()], &offsets, &[0, 1]);
+        let include = |team: &[(f64, Message)], updates: &[Message]| {
+            team.iter()
+                .zip(updates)
+                .map(|((_, skill), update)| skill.include(update))
+                .collect()
+        };
+        let offset_after = |team: usize| Some(offsets[team]?.include(&offset_updates[team]?));
+        (
+            include(winners, &updates[0]),
+            include(losers, &updates[1]),
+            [offset_after(0), offset_after(1)],
+        )
+    }
+
+    /// Largest change of the natural parameters between two messages.
+    fn message_change(old: &Message, new: &Message) -> f64 {
+        (new.pi - old.pi).abs().max((new.tau - old.tau).abs())
+    }
+}
+
+impl RatingSystem for TrueSkill {
+    type Rating = Message;
+
+    fn initial(&self) -> Message {
+        Message::from_mu_sigma2(Player::default_mean(), Player::default_sigma().powi(2))
+    }
+
+    fn rate(&self, winners: &[Message], losers: &[Message]) -> (Vec<Message>, Vec<Message>) {
+        let (winner_updates, loser_updates) = self.tree_pass(winners, losers, GameResult::Won);
+        let include = |skills: &[Message], updates: Vec<Message>| {
+            skills
                 .iter()
-                .map(|message| self.to_skill(message))
+                .zip(updates)
+                .map(|(skill, update)| skill.include(&update))
+                .collect()
+        };
+        (
+            include(winners, winner_updates),
+            include(losers, loser_updates),
+        )
+    }
+
+    fn rate_weighted(
+        &self,
+        winners: &[(f64, Message)],
+        losers: &[(f64, Message)],
+    ) -> (Vec<Message>, Vec<Message>) {
+        let (winners, losers, _) = self.rate_with_offsets(winners, losers, [None, None]);
+        (winners, losers)
+    }
+
+    /// Conservative estimate two standard deviations below the mean.
+    fn score(&self, rating: &Message) -> f64 {
+        let (mu, sigma2) = rating.to_mu_sigma2();
+        mu - 2.0 * sigma2.sqrt()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::HashSet;
+
+    fn true_skill() -> TrueSkill {
+        TrueSkill::new(25.0 / 6.0, 0.0)
+    }
+
+    fn prior() -> Message {
+        Message::from_mu_sigma2(25.0, (25.0_f64 / 3.0).powi(2))
+    }
+
+    fn posterior(update: &Message) -> (f64, f64) {
+        let (mu, sigma2) = prior().include(update).to_mu_sigma2();
+        (mu, sigma2.sqrt())
+    }
+
+    fn assert_close(actual: (f64, f64), expected: (f64, f64)) {
+        assert!(
+            (actual.0 - expected.0).abs() < 1e-3 && (actual.1 - expected.1).abs() < 1e-3,
+            "{:?} is not {:?}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn test_tree_pass() {
+        let (winners, losers) = true_skill().tree_pass(&[prior()], &[prior()], GameResult::Won);
+        assert_close(posterior(&winners[0]), (29.205, 7.194));
+        assert_close(posterior(&losers[0]), (20.795, 7.194));
+
+        let (losers, winners) = true_skill().tree_pass(&[prior()], &[prior()], GameResult::Lost);
+        assert_close(posterior(&winners[0]), (29.205, 7.194));
+        assert_close(posterior(&losers[0]), (20.795, 7.194));
+    }
+
+    #[test]
+    fn test_rank_pass_free_for_all() {
+        let team = vec![(1.0, prior())];
+        let updates = true_skill().rank_pass(&[team.clone(), team.clone(), team], &[1, 0, 2]);
+        assert_close(posterior(&updates[1][0]), (31.311, 6.699));
+        assert_close(posterior(&updates[0][0]), (25.0, 6.238));
+        assert_close(posterior(&updates[2][0]), (18.689, 6.699));
+    }
+
+    #[test]
+    fn test_rank_pass_partial_play() {
+        let updates = true_skill().rank_pass(
+            &[
+                vec![(1.0, prior()), (0.5, prior())],
+                vec![(1.0, prior()), (0.0, prior())],
+            ],
+            &[0, 1],
+        );
+        assert_close(posterior(&updates[0][0]), (26.632, 7.716));
+        assert_close(posterior(&updates[0][1]), (25.816, 8.183));
+        assert_close(posterior(&updates[1][0]), (23.368, 7.716));
+        // The substitute that never played learns nothing.
+        assert_close(posterior(&updates[1][1]), (25.0, 25.0 / 3.0));
+    }
+
+    #[test]
+    fn test_rate_weighted() {
+        // Players of the whole game are rated like by `rate`.
+        let (winners, losers) = true_skill().rate_weighted(&[(1.0, prior())], &[(1.0, prior())]);
+        let (expected_winners, expected_losers) = true_skill().rate(&[prior()], &[prior()]);
+        assert_close(
+            winners[0].to_mu_sigma2(),
+            expected_winners[0].to_mu_sigma2(),
+        );
+        assert_close(losers[0].to_mu_sigma2(), expected_losers[0].to_mu_sigma2());
+    }
+
+    /// Means, standard deviations and draw margins from ordinary to absurd.
+    const MUS: [f64; 9] = [-1e4, -1e3, -100.0, -30.0, 0.0, 30.0, 100.0, 1e3, 1e4];
+    const SIGMAS: [f64; 5] = [1e-3, 0.1, 25.0 / 3.0, 100.0, 1e3];
+    const DRAW_MARGINS: [f64; 3] = [0.0, 0.5, 10.0];
+
+    fn assert_finite(updates: &[Message]) {
+        for update in updates {
+            assert!(
+                update.pi >= 0.0 && update.pi.is_finite() && update.tau.is_finite(),
+                "{:?} is invalid",
+                update
+            );
+        }
+    }
+
+    /// Checks that the players stay finite after including their updates.
+    fn assert_rated_finite(skills: &[Message], updates: &[Message]) {
+        for (skill, update) in skills.iter().zip(updates) {
+            let (mu, sigma2) = skill.include(update).to_mu_sigma2();
+            assert!(
+                mu.is_finite() && sigma2.is_finite() && sigma2 > 0.0,
+                "{:?} updated by {:?} is invalid",
+                skill,
+                update
+            );
+        }
+    }
+
+    #[test]
+    fn test_won_terms_continuous() {
+        let below = TrueSkill::won_terms(ASYMPTOTIC_LIMIT - 1e-9);
+        let above = TrueSkill::won_terms(ASYMPTOTIC_LIMIT + 1e-9);
+        assert!((below.0 - above.0).abs() < 1e-6);
+        assert!((below.1 - above.1).abs() < 1e-6);
+        for x in [-1e300, -1e8, -1e4, -40.0, -30.0, -5.0, 0.0, 5.0, 40.0] {
+            let (v, v_plus_x) = TrueSkill::won_terms(x);
+            assert!(v.is_finite() && v >= 0.0);
+            // Both factors of w lie in [0, 1] for w itself to do so.
+            assert!((0.0..=1.0).contains(&(v * v_plus_x)));
+        }
+    }
+
+    #[test]
+    fn test_updates_stay_finite() {
+        for eps in DRAW_MARGINS {
+            let true_skill = TrueSkill::new(25.0 / 6.0, eps);
+            for mu in MUS {
+                for sigma in SIGMAS {
+                    let left = Message::from_mu_sigma2(mu, sigma.powi(2));
+                    let right = Message::from_mu_sigma2(-mu, sigma.powi(2));
+                    for result in [GameResult::Won, GameResult::Lost] {
+                        let (left_updates, right_updates) =
+                            true_skill.tree_pass(&[left, prior()], &[right], result);
+                        assert_finite(&left_updates);
+                        assert_finite(&right_updates);
+                    }
+                    if eps > 0.0 {
+                        let (left_updates, right_updates) =
+                            true_skill.tree_pass(&[left], &[right], GameResult::Draw);
+                        assert_finite(&left_updates);
+                        assert_finite(&right_updates);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_rank_pass_stays_finite() {
+        for mu in MUS {
+            for sigma in SIGMAS {
+                let team = |mu: f64| vec![(1.0, Message::from_mu_sigma2(mu, sigma.powi(2)))];
+                let teams = [team(-mu), team(0.0), team(mu)];
+                let updates = true_skill().rank_pass(&teams, &[0, 1, 2]);
+                for (team, team_updates) in teams.iter().zip(&updates) {
+                    assert_finite(team_updates);
+                    let skills = team.iter().map(|(_, skill)| *skill).collect::<Vec<_>>();
+                    assert_rated_finite(&skills, team_updates);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_random_games_stay_finite() {
+        use rand::{Rng, SeedableRng};
+
+        let mut rng = rand::rngs::StdRng::seed_from_u64(0x5eed);
+        // Spans many orders of magnitude of means and standard deviations.
+        let skill = |rng: &mut rand::rngs::StdRng| {
+            let mu = rng.gen_range(-1.0..1.0) * 10_f64.powf(rng.gen_range(-2.0..4.0));
+            let sigma = 10_f64.powf(rng.gen_range(-3.0..3.0));
+            Message::from_mu_sigma2(mu, sigma.powi(2))
+        };
+        for _ in 0..1000 {
+            let eps = if rng.gen_bool(0.5) {
+                0.0
+            } else {
+                rng.gen_range(0.0..10.0)
+            };
+            let true_skill = TrueSkill::new(rng.gen_range(0.1..10.0), eps);
+            let team = |rng: &mut rand::rngs::StdRng| {
+                (0..rng.gen_range(1..=4))
+                    .map(|_| skill(rng))
+                    .collect::<Vec<_>>()
+            };
+            let (left, right) = (team(&mut rng), team(&mut rng));
+            for result in [GameResult::Won, GameResult::Draw, GameResult::Lost] {
+                if matches!(result, GameResult::Draw) && eps == 0.0 {
+                    continue;
+                }
+                let (left_updates, right_updates) = true_skill.tree_pass(&left, &right, result);
+                assert_finite(&left_updates);
+                assert_finite(&right_updates);
+                assert_rated_finite(&left, &left_updates);
+                assert_rated_finite(&right, &right_updates);
+            }
+
+            let teams = (0..rng.gen_range(2..=4))
+                .map(|_| {
+                    team(&mut rng)
+                        .into_iter()
+                        .map(|skill| (rng.gen_range(0.01..=1.0), skill))
+                        .collect::<Vec<_>>()
+                })
                 .collect::<Vec<_>>();
-        (left_skills, right_skills)
+            let ranks = (0..teams.len())
+                .map(|_| rng.gen_range(0..teams.len()))
+                .collect::<Vec<_>>();
+            if eps == 0.0 && ranks.iter().collect::<HashSet<_>>().len() < ranks.len() {
+                continue;
+            }
+            for (team, team_updates) in teams.iter().zip(true_skill.rank_pass(&teams, &ranks)) {
+                assert_finite(&team_updates);
+                let skills = team.iter().map(|(_, skill)| *skill).collect::<Vec<_>>();
+                assert_rated_finite(&skills, &team_updates);
+            }
+        }
+    }
+
+    #[test]
+    fn test_rank_pass_with_offsets() {
+        let team = vec![(1.0, prior())];
+        let offset = Message::from_mu_sigma2(0.0, 4.0);
+        let (updates, offset_updates) = true_skill().rank_pass_with_offsets(
+            &[team.clone(), team],
+            &[Some(offset), None],
+            &[0, 1],
+        );
+        // Winning with an advantage of unknown size teaches less about the
+        // skill of the winner, but something about the advantage.
+        let (winner_mu, _) = posterior(&updates[0][0]);
+        assert!(winner_mu > 25.0 && winner_mu < 29.205);
+        assert!(offset_updates[1].is_none());
+        let (offset_mu, offset_sigma2) = offset.include(&offset_updates[0].unwrap()).to_mu_sigma2();
+        assert!(offset_mu > 0.0 && offset_sigma2 < 4.0);
+
+        // Without offsets the result is the same as for a plain game.
+        let team = vec![(1.0, prior())];
+        let (updates, offset_updates) =
+            true_skill().rank_pass_with_offsets(&[team.clone(), team], &[None, None], &[1, 0]);
+        assert_close(posterior(&updates[1][0]), (29.205, 7.194));
+        assert_eq!(offset_updates.len(), 2);
     }
 }
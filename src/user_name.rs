@@ -0,0 +1,183 @@
+use std::fmt;
+
+use serde::Serialize;
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Minimal number of characters in a user name.
+pub const MIN_LENGTH: usize = 3;
+/// Maximal number of characters in a user name.
+pub const MAX_LENGTH: usize = 32;
+
+/// Describes the rule a user name violates.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+#[serde(tag = "rule", rename_all = "snake_case")]
+pub enum NameError {
+    /// The name has fewer than `min_length` characters.
+    TooShort { min_length: usize },
+    /// The name has more than `max_length` characters.
+    TooLong { max_length: usize },
+    /// The name contains a character that is not allowed.
+    ForbiddenCharacter { character: char },
+}
+
+impl fmt::Display for NameError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            NameError::TooShort { min_length } => {
+                write!(f, "name must have at least {} characters", min_length)
+            }
+            NameError::TooLong { max_length } => {
+                write!(f, "name must have at most {} characters", max_length)
+            }
+            NameError::ForbiddenCharacter { character } => {
+                write!(f, "name must not contain {:?}", character)
+            }
+        }
+    }
+}
+
+/// Validates a user name and returns it without surrounding whitespace.
+///
+/// Lengths are counted in user-perceived characters. The index separator `:`
+/// and control characters are not allowed, also not in their compatibility
+/// forms.
+pub fn validate(name: &str) -> Result<String, NameError> {
+    let name = name.trim();
+
+    if let Some(character) = name.chars().find(|c| c.is_control()) {
+        return Err(NameError::ForbiddenCharacter { character });
+    }
+    if let Some(character) = name
+        .chars()
+        .find(|c| c.to_string().nfkd().any(|d| d == ':'))
+    {
+        return Err(NameError::ForbiddenCharacter { character });
+    }
+
+    let length = name.graphemes(true).count();
+    if length < MIN_LENGTH {
+        return Err(NameError::TooShort {
+            min_length: MIN_LENGTH,
+        });
+    }
+    if length > MAX_LENGTH {
+        return Err(NameError::TooLong {
+            max_length: MAX_LENGTH,
+        });
+    }
+
+    Ok(name.to_owned())
+}
+
+/// Folds a name into the form that is used for searching: lowercased and
+/// with all accents removed.
+pub fn normalize(name: &str) -> String {
+    name.nfkd()
+        .filter(|c| !is_combining_mark(*c))
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Splits a name into its normalized words.
+pub fn tokenize(name: &str) -> Vec<String> {
+    normalize(name)
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|token| !token.is_empty())
+        .map(|token| token.to_owned())
+        .collect()
+}
+
+/// Scores how well a name matches a search query. Higher scores are better
+/// matches.
+///
+/// Returns `None` if not every word of the query is a prefix of some word of
+/// the name.
+pub fn relevance(name: &str, query: &str) -> Option<u32> {
+    let normalized_name = normalize(name);
+    let normalized_query = normalize(query);
+    if normalized_name == normalized_query {
+        return Some(3);
+    }
+    if normalized_name.starts_with(&normalized_query) {
+        return Some(2);
+    }
+    let name_tokens = tokenize(name);
+    if tokenize(query).iter().all(|query_token| {
+        name_tokens
+            .iter()
+            .any(|name_token| name_token.starts_with(query_token))
+    }) {
+        Some(1)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(normalize("Martin Kiefel"), "martin kiefel");
+        assert_eq!(normalize("Jürgen Ñúñez"), "jurgen nunez");
+    }
+
+    #[test]
+    fn test_tokenize() {
+        assert_eq!(
+            tokenize("  Anne-Marie  Élise "),
+            vec!["anne", "marie", "elise"]
+        );
+        assert!(tokenize(" - ").is_empty());
+    }
+
+    #[test]
+    fn test_validate() {
+        assert_eq!(validate("  Max "), Ok("Max".to_owned()));
+        assert_eq!(validate("Zoë"), Ok("Zoë".to_owned()));
+        assert_eq!(
+            validate("Zo\u{0308}"),
+            Err(NameError::TooShort {
+                min_length: MIN_LENGTH
+            }),
+            "combining marks do not count as characters of their own"
+        );
+        assert_eq!(
+            validate(" ab "),
+            Err(NameError::TooShort {
+                min_length: MIN_LENGTH
+            })
+        );
+        assert_eq!(
+            validate(&"a".repeat(MAX_LENGTH + 1)),
+            Err(NameError::TooLong {
+                max_length: MAX_LENGTH
+            })
+        );
+        assert_eq!(
+            validate("max:1"),
+            Err(NameError::ForbiddenCharacter { character: ':' })
+        );
+        assert_eq!(
+            validate("max\u{ff1a}1"),
+            Err(NameError::ForbiddenCharacter {
+                character: '\u{ff1a}'
+            })
+        );
+        assert_eq!(
+            validate("max\t1"),
+            Err(NameError::ForbiddenCharacter { character: '\t' })
+        );
+    }
+
+    #[test]
+    fn test_relevance() {
+        assert_eq!(relevance("Max", "max"), Some(3));
+        assert_eq!(relevance("Martin Kiefel", "mart"), Some(2));
+        assert_eq!(relevance("Martin Kiefel", "kiefel"), Some(1));
+        assert_eq!(relevance("Martin Kiefel", "kie mar"), Some(1));
+        assert_eq!(relevance("Martin Kiefel", "kiefer"), None);
+    }
+}
@@ -0,0 +1,358 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use hmac::{Hmac, Mac};
+use rocket::tokio::{self, sync::Semaphore};
+use rocket_db_pools::deadpool_redis::Pool;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+use crate::skill_base::{self, GroupEvent, GroupId, WebhookId};
+
+/// How often a delivery is attempted before giving up.
+const MAX_ATTEMPTS: u32 = 5;
+
+/// Number of deliveries that are attempted at the same time.
+const MAX_CONCURRENT_DELIVERIES: usize = 16;
+
+/// Time after which the lock of a worker is released unless the worker
+/// extends it. Has to be longer than one round of the worker loop.
+const WORKER_LOCK_EXPIRY: Duration = Duration::from_secs(30);
+
+/// How long a worker waits before trying to take the lock again.
+const WORKER_LOCK_RETRY: Duration = Duration::from_secs(10);
+
+/// Registration to deliver the events of a group to a URL.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Webhook {
+    id: WebhookId,
+    url: String,
+    /// Shared secret that signs all payloads.
+    secret: String,
+    /// Names of the events to deliver. All events are delivered if empty.
+    events: Vec<String>,
+}
+
+impl Webhook {
+    pub fn new(id: WebhookId, url: String, secret: String, events: Vec<String>) -> Self {
+        Webhook {
+            id,
+            url,
+            secret,
+            events,
+        }
+    }
+
+    pub fn id(&self) -> &WebhookId {
+        &self.id
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub fn events(&self) -> &Vec<String> {
+        &self.events
+    }
+
+    /// Whether the event should be delivered to this webhook.
+    pub fn accepts(&self, event: &GroupEvent) -> bool {
+        self.events.is_empty() || self.events.iter().any(|name| name == event.name())
+    }
+}
+
+/// Event that waits to be delivered to a webhook.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Delivery {
+    id: String,
+    group_id: GroupId,
+    webhook: Webhook,
+    event: GroupEvent,
+}
+
+impl Delivery {
+    pub fn new(group_id: GroupId, webhook: Webhook, event: GroupEvent) -> Self {
+        Delivery {
+            id: uuid::Uuid::new_v4().simple().to_string(),
+            group_id,
+            webhook,
+            event,
+        }
+    }
+
+    pub fn group_id(&self) -> &GroupId {
+        &self.group_id
+    }
+
+    pub fn webhook(&self) -> &Webhook {
+        &self.webhook
+    }
+}
+
+/// Outcome of a delivery.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct DeliveryStatus {
+    delivery_id: String,
+    event: String,
+    datetime: chrono::DateTime<chrono::Utc>,
+    attempts: u32,
+    delivered: bool,
+    /// HTTP status of the last response, if there was one.
+    status: Option<u16>,
+    /// Describes why the last attempt failed.
+    error: Option<String>,
+}
+
+/// Signs a payload with HMAC-SHA256 and returns the hex encoded signature.
+pub fn sign(secret: &str, payload: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(payload);
+    mac.finalize()
+        .into_bytes()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Sends a delivery once. Returns the status of the response, which is an error
+/// if it is not a success.
+async fn send(client: &reqwest::Client, delivery: &Delivery) -> Result<u16, (Option<u16>, String)> {
+    let payload = serde_json::to_vec(&delivery.event).map_err(|err| (None, err.to_string()))?;
+    let response = client
+        .post(&delivery.webhook.url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header("X-Fooskill-Event", delivery.event.name())
+        .header("X-Fooskill-Delivery", &delivery.id)
+        .header(
+            "X-Fooskill-Signature",
+            "sha256=".to_owned() + &sign(&delivery.webhook.secret, &payload),
+        )
+        .body(payload)
+        .send()
+        .await
+        .map_err(|err| (None, err.to_string()))?;
+
+    let status = response.status();
+    if status.is_success() {
+        Ok(status.as_u16())
+    } else {
+        Err((Some(status.as_u16()), status.to_string()))
+    }
+}
+
+/// Attempts a delivery until it succeeds, waiting exponentially longer between
+/// attempts. Returns the final status.
+async fn deliver(
+    client: &reqwest::Client,
+    delivery: &Delivery,
+    backoff: Duration,
+) -> DeliveryStatus {
+    let mut attempts = 0;
+    loop {
+        attempts += 1;
+        let result = send(client, delivery).await;
+        if result.is_ok() || attempts == MAX_ATTEMPTS {
+            let (status, error) = match result {
+                Ok(status) => (Some(status), None),
+                Err((status, error)) => (status, Some(error)),
+            };
+            return DeliveryStatus {
+                delivery_id: delivery.id.clone(),
+                event: delivery.event.name().to_owned(),
+                datetime: chrono::Utc::now(),
+                attempts,
+                delivered: error.is_none(),
+                status,
+                error,
+            };
+        }
+        tokio::time::sleep(backoff * 2_u32.pow(attempts - 1)).await;
+    }
+}
+
+/// Delivers all queued webhook events until the process ends.
+///
+/// Every server runs a worker, but only the one holding the worker lock takes
+/// deliveries from the queue. Deliveries that were in progress when the last
+/// worker stopped are attempted again once the lock is taken.
+pub async fn run(pool: Pool) {
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()
+        .expect("HTTP client can be built");
+    let permits = Arc::new(Semaphore::new(MAX_CONCURRENT_DELIVERIES));
+    let worker_id = uuid::Uuid::new_v4().simple().to_string();
+    let mut locked = false;
+    loop {
+        let mut con = match pool.get().await {
+            Ok(con) => con,
+            Err(err) => {
+                rocket::error!("webhook worker has no connection: {:?}", err);
+                tokio::time::sleep(Duration::from_secs(5)).await;
+                continue;
+            }
+        };
+        match skill_base::lock_webhook_worker(&mut con, &worker_id, WORKER_LOCK_EXPIRY).await {
+            Ok(true) => {}
+            Ok(false) => {
+                locked = false;
+                tokio::time::sleep(WORKER_LOCK_RETRY).await;
+                continue;
+            }
+            Err(err) => {
+                locked = false;
+                rocket::error!("webhook worker failed to take the lock: {:?}", err);
+                tokio::time::sleep(WORKER_LOCK_RETRY).await;
+                continue;
+            }
+        }
+        if !locked {
+            match skill_base::requeue_webhook_deliveries(&mut con).await {
+                Ok(_) => locked = true,
+                Err(err) => {
+                    rocket::error!("webhook worker failed to requeue deliveries: {:?}", err);
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                    continue;
+                }
+            }
+        }
+        // Only take a delivery from the queue once it can be attempted. Stop
+        // waiting in time to extend the lock.
+        let permit =
+            match tokio::time::timeout(Duration::from_secs(5), permits.clone().acquire_owned())
+                .await
+            {
+                Ok(permit) => permit.expect("semaphore is never closed"),
+                Err(_) => continue,
+            };
+        let (delivery, entry) = match skill_base::next_webhook_delivery(&mut con).await {
+            Ok(Some(next)) => next,
+            Ok(None) => continue,
+            Err(err) => {
+                rocket::error!("webhook worker failed to read queue: {:?}", err);
+                tokio::time::sleep(Duration::from_secs(5)).await;
+                continue;
+            }
+        };
+
+        let pool = pool.clone();
+        let client = client.clone();
+        tokio::spawn(async move {
+            let status = deliver(&client, &delivery, Duration::from_secs(1)).await;
+            let result = match pool.get().await {
+                Ok(mut con) => {
+                    skill_base::record_webhook_delivery(&mut con, &delivery, &entry, &status)
+                        .await
+                        .map_err(|err| format!("{:?}", err))
+                }
+                Err(err) => Err(format!("{:?}", err)),
+            };
+            if let Err(err) = result {
+                rocket::error!("failed to record delivery {:?}: {}", delivery.id, err);
+            }
+            drop(permit);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rocket::tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use rocket::tokio::net::TcpListener;
+
+    #[test]
+    fn test_sign() {
+        // Test case 2 of RFC 4231.
+        assert_eq!(
+            sign("Jefe", b"what do ya want for nothing?"),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    /// Answers a single HTTP request with the given status line and returns the
+    /// raw request.
+    async fn serve_once(listener: &TcpListener, status_line: &str) -> String {
+        let (mut socket, _) = listener.accept().await.unwrap();
+        let mut request = Vec::new();
+        let mut buffer = [0_u8; 4096];
+        // Read until the JSON body is complete.
+        while !request.ends_with(b"}") {
+            let read = socket.read(&mut buffer).await.unwrap();
+            if read == 0 {
+                break;
+            }
+            request.extend_from_slice(&buffer[..read]);
+        }
+        socket
+            .write_all(format!("{}\r\ncontent-length: 0\r\n\r\n", status_line).as_bytes())
+            .await
+            .unwrap();
+        String::from_utf8(request).unwrap()
+    }
+
+    fn local_delivery(url: String) -> Delivery {
+        let user: skill_base::User = serde_json::from_value(serde_json::json!({
+            "id": "user",
+            "name": "Max",
+            "player": {
+                "skill": { "pi": 1.0, "tau": 25.0 },
+                "datetime": "2022-01-01T00:00:00Z",
+            },
+        }))
+        .unwrap();
+        Delivery::new(
+            serde_json::from_value::<GroupId>(serde_json::json!("group")).unwrap(),
+            Webhook::new(
+                WebhookId::from("webhook".to_owned()),
+                url,
+                "secret".to_owned(),
+                vec![],
+            ),
+            GroupEvent::UserCreated { user },
+        )
+    }
+
+    #[rocket::async_test]
+    async fn test_deliver_signed() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let delivery = local_delivery(format!("http://{}/", listener.local_addr().unwrap()));
+
+        let client = reqwest::Client::new();
+        let (status, request) = tokio::join!(
+            deliver(&client, &delivery, Duration::from_millis(1)),
+            serve_once(&listener, "HTTP/1.1 204 No Content"),
+        );
+
+        assert!(status.delivered);
+        assert_eq!(status.attempts, 1);
+        assert_eq!(status.status, Some(204));
+        let payload = serde_json::to_vec(&delivery.event).unwrap();
+        let signature = format!("sha256={}", sign("secret", &payload));
+        assert!(request
+            .to_lowercase()
+            .contains("x-fooskill-event: user_created"));
+        assert!(request.contains(&signature));
+    }
+
+    #[rocket::async_test]
+    async fn test_deliver_retries() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let delivery = local_delivery(format!("http://{}/", listener.local_addr().unwrap()));
+
+        let client = reqwest::Client::new();
+        let (status, _) = tokio::join!(
+            deliver(&client, &delivery, Duration::from_millis(1)),
+            async {
+                // The first attempt fails, so the delivery has to come back.
+                serve_once(&listener, "HTTP/1.1 500 Internal Server Error").await;
+                serve_once(&listener, "HTTP/1.1 200 OK").await
+            },
+        );
+
+        assert!(status.delivered);
+        assert_eq!(status.attempts, 2);
+    }
+}